@@ -0,0 +1,80 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Measures how many instructions per second the CPU core runs.
+//!
+//! Run with `cargo run --release --example benchmark`, adding
+//! `--features block-cache` to compare with the block cache.
+
+extern crate snesemu_cpu;
+
+use std::time::{Duration, Instant};
+
+use snesemu_cpu::cpu::CPU;
+use snesemu_cpu::mapper::LoROM;
+
+const INSTRUCTIONS: u32 = 20_000_000;
+
+const PROGRAM: &[u8] = &[
+    0x18,             // 8000: CLC
+    0xFB,             // 8001: XCE
+    0xC2, 0x30,       // 8002: REP #$30
+    0xA2, 0x00, 0x00, // 8004: LDX #$0000
+    0xA9, 0x00, 0x00, // 8007: LDA #$0000
+    0x7D, 0x00, 0x10, // 800A: ADC $1000,X
+    0x9D, 0x00, 0x10, // 800D: STA $1000,X
+    0xE8,             // 8010: INX
+    0xE8,             // 8011: INX
+    0xE0, 0x00, 0x01, // 8012: CPX #$0100
+    0xD0, 0xF3,       // 8015: BNE $800A
+    0x20, 0x20, 0x80, // 8017: JSR $8020
+    0xE2, 0x20,       // 801A: SEP #$20
+    0xC2, 0x20,       // 801C: REP #$20
+    0x80, 0xE4,       // 801E: BRA $8004
+    0xA5, 0x10,       // 8020: LDA $10
+    0x0A,             // 8022: ASL A
+    0x85, 0x10,       // 8023: STA $10
+    0x60,             // 8025: RTS
+];
+
+fn main() {
+    let mut rom = vec![0; 0x8000];
+    rom[..PROGRAM.len()].copy_from_slice(PROGRAM);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+
+    let start = Instant::now();
+    for _ in 0..INSTRUCTIONS {
+        cpu.step().unwrap();
+    }
+    report("step", start.elapsed());
+
+    // Runs the same instructions, as they take the same number of cycles.
+    let end = cpu.cycles;
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    let start = Instant::now();
+    cpu.run_until(end).unwrap();
+    report("run_until", start.elapsed());
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+    println!("{}: {} instructions in {:.3} s, {:.0} instructions per second",
+             name,
+             INSTRUCTIONS,
+             seconds,
+             INSTRUCTIONS as f64 / seconds);
+}
@@ -15,34 +15,56 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use cpu::CPU;
+use dma;
 use mapper::Mapper;
 
-pub trait BitWidth {
-    fn read<M: Mapper>(cpu: &CPU<M>, bank: u8, address: u16) -> Self;
+pub trait BitWidth: Copy + Into<u16> {
+    /// Number of bits in a value.
+    const BITS: u32;
+
+    fn read<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16) -> Self;
     fn write<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16, value: Self);
+    /// Reads a value which may continue into the next bank.
+    fn read_long<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16) -> Self;
+    /// Writes a value which may continue into the next bank.
+    fn write_long<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16, value: Self);
     fn get(value: u16) -> Self;
     fn set(value: &mut u16, to: Self);
 }
 
+fn next_long(bank: u8, address: u16) -> (u8, u16) {
+    match address.checked_add(1) {
+        Some(address) => (bank, address),
+        None => (bank.wrapping_add(1), 0),
+    }
+}
+
 impl BitWidth for u8 {
-    fn read<M: Mapper>(cpu: &CPU<M>, bank: u8, address: u16) -> u8 {
-        let address = address as usize;
-        match (bank, address) {
-            (0x00...0x3F, 0x0000...0x1FFF) |
-            (0x7E, _) => cpu.ram[address],
-            (0x7F, _) => cpu.ram[0x10000 + address],
-            _ => cpu.mapper.read(bank, address as u16),
-        }
+    const BITS: u32 = 8;
+
+    fn read<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16) -> u8 {
+        let cycles = cpu.bus.access_cycles(bank, address);
+        cpu.spend(cycles);
+        cpu.bus.read(bank, address)
     }
 
     fn write<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16, value: u8) {
-        let address = address as usize;
-        match (bank, address) {
-            (0x00...0x3F, 0x0000...0x1FFF) |
-            (0x7E, _) => cpu.ram[address] = value,
-            (0x7F, _) => cpu.ram[0x10000 + address] = value,
-            _ => cpu.mapper.write(bank, address as u16, value),
-        };
+        let cycles = cpu.bus.access_cycles(bank, address);
+        cpu.spend(cycles);
+        if let Err(error) = cpu.bus.write(bank, address, value) {
+            cpu.fault(error);
+        }
+        if cpu.bus.dma.mdmaen != 0 {
+            dma::run(cpu);
+        }
+    }
+
+    fn read_long<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16) -> u8 {
+        u8::read(cpu, bank, address)
+    }
+
+    fn write_long<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16, value: u8) {
+        u8::write(cpu, bank, address, value);
     }
 
     fn get(value: u16) -> u8 {
@@ -55,7 +77,9 @@ impl BitWidth for u8 {
 }
 
 impl BitWidth for u16 {
-    fn read<M: Mapper>(cpu: &CPU<M>, bank: u8, address: u16) -> u16 {
+    const BITS: u32 = 16;
+
+    fn read<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16) -> u16 {
         let a = u8::read(cpu, bank, address) as u16;
         let b = (u8::read(cpu, bank, address.wrapping_add(1)) as u16) << 8;
         a | b
@@ -66,6 +90,19 @@ impl BitWidth for u16 {
         u8::write(cpu, bank, address.wrapping_add(1), (value >> 8) as u8);
     }
 
+    fn read_long<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16) -> u16 {
+        let (next_bank, next_address) = next_long(bank, address);
+        let a = u8::read(cpu, bank, address) as u16;
+        let b = (u8::read(cpu, next_bank, next_address) as u16) << 8;
+        a | b
+    }
+
+    fn write_long<M: Mapper>(cpu: &mut CPU<M>, bank: u8, address: u16, value: u16) {
+        let (next_bank, next_address) = next_long(bank, address);
+        u8::write(cpu, bank, address, value as u8);
+        u8::write(cpu, next_bank, next_address, (value >> 8) as u8);
+    }
+
     fn get(value: u16) -> u16 {
         value
     }
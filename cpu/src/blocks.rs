@@ -0,0 +1,219 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cache of decoded basic blocks, enabled with the `block-cache` feature.
+//!
+//! A block is a run of straight-line code starting at PB:PC, decoded
+//! with the opcode table for the current M, X and E flags. Running
+//! a cached block skips decoding opcodes, but otherwise does exactly
+//! what `CPU::step` would do, stopping early whenever control flow
+//! leaves the block, register widths change, an interrupt becomes
+//! pending or the code of the block gets overwritten. Code outside of
+//! memory, such as in I/O registers or open bus, is never cached.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+
+use bus;
+use cpu::{CPU, Registers, State, FLAG_A16, FLAG_NO_IRQ, FLAG_XY16};
+use error::Error;
+use mapper::Mapper;
+use opcodes::{self, AddressingMode, Instruction};
+
+/// Maximum number of instructions in a block.
+const BLOCK_SIZE: usize = 64;
+
+/// Number of pages identified by `bus::page`.
+const PAGES: usize = 0x200 + 0x10000;
+
+/// Identifies a block by its address and the opcode table used to decode it.
+fn key(registers: &Registers) -> u32 {
+    let widths = if registers.emulation {
+        0
+    } else {
+        1 + registers.flags.contains(FLAG_A16) as u32 +
+        2 * registers.flags.contains(FLAG_XY16) as u32
+    };
+    widths << 24 | (registers.pb as u32) << 16 | registers.pc as u32
+}
+
+/// Hashes keys, which are already well distributed in their low bits.
+#[derive(Default)]
+struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0 << 8 | byte as u64;
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.0 = value as u64;
+    }
+}
+
+struct Cached<M: Mapper> {
+    handler: fn(&mut CPU<M>),
+    opcode: u8,
+    /// Key of the block continuing after this instruction.
+    next: u32,
+}
+
+struct Block<M: Mapper> {
+    instructions: Vec<Cached<M>>,
+}
+
+pub struct Blocks<M: Mapper> {
+    blocks: HashMap<u32, Arc<Block<M>>, BuildHasherDefault<KeyHasher>>,
+    /// Keys of blocks decoded from each page.
+    pages: HashMap<u32, Vec<u32>, BuildHasherDefault<KeyHasher>>,
+    /// One bit for every page holding cached code.
+    code: Vec<u64>,
+    /// Whether a block was invalidated since the current block started.
+    invalidated: bool,
+}
+
+impl<M: Mapper> Blocks<M> {
+    pub fn new() -> Self {
+        Blocks {
+            blocks: HashMap::default(),
+            pages: HashMap::default(),
+            code: vec![0; PAGES / 64],
+            invalidated: false,
+        }
+    }
+
+    /// Drops blocks decoded from a page after it was written to.
+    pub fn invalidate(&mut self, page: u32) {
+        let (index, bit) = (page as usize / 64, 1 << (page % 64));
+        if self.code[index] & bit == 0 {
+            return;
+        }
+        self.code[index] &= !bit;
+        for key in self.pages.remove(&page).unwrap_or_default() {
+            self.blocks.remove(&key);
+        }
+        self.invalidated = true;
+    }
+
+    fn insert(&mut self, key: u32, block: Arc<Block<M>>, pages: &[u32]) {
+        for &page in pages {
+            self.code[page as usize / 64] |= 1 << (page % 64);
+            self.pages.entry(page).or_default().push(key);
+        }
+        self.blocks.insert(key, block);
+    }
+}
+
+/// Whether an instruction may jump, or change which opcode table is used.
+fn ends_block<M: Mapper>(instruction: &Instruction<M>) -> bool {
+    match instruction.mode {
+        AddressingMode::Relative | AddressingMode::RelativeLong | AddressingMode::BlockMove => {
+            true
+        }
+        _ => {
+            matches!(instruction.mnemonic,
+                     "JMP" | "JML" | "JSR" | "JSL" | "RTS" | "RTL" | "RTI" | "BRK" | "COP" |
+                     "WAI" | "STP" | "REP" | "SEP" | "XCE" | "PLP")
+        }
+    }
+}
+
+/// Decodes a block at PB:PC, along with pages it was decoded from.
+fn decode<M: Mapper>(cpu: &CPU<M>) -> (Block<M>, Vec<u32>) {
+    let table = opcodes::table::<M>(&cpu.registers);
+    let pb = cpu.registers.pb;
+    let mut pc = cpu.registers.pc;
+    let base = key(&cpu.registers) & !0xFFFF;
+    let mut instructions = Vec::new();
+    let mut pages = Vec::new();
+    while instructions.len() < BLOCK_SIZE {
+        // Operands are read when running instructions, so only opcodes
+        // need to be in memory which can be cached.
+        let opcode = match cpu.bus.memory(pb, pc) {
+            Some(opcode) => opcode,
+            None => break,
+        };
+        let page = bus::page(pb, pc);
+        if !pages.contains(&page) {
+            pages.push(page);
+        }
+        let instruction = &table[opcode as usize];
+        pc = pc.wrapping_add(instruction.length());
+        instructions.push(Cached {
+            handler: instruction.handler,
+            opcode,
+            next: base | pc as u32,
+        });
+        if ends_block(instruction) {
+            break;
+        }
+    }
+    (Block { instructions }, pages)
+}
+
+/// Whether the next step would run an instruction at PB:PC.
+fn running<M: Mapper>(cpu: &CPU<M>) -> bool {
+    cpu.state == State::Running && !cpu.nmi && !cpu.bus.io.nmi &&
+    (!cpu.irq_line() || cpu.registers.flags.contains(FLAG_NO_IRQ))
+}
+
+/// Runs a block of instructions, stopping early when `cycles` reaches
+/// `end`. This is equivalent to calling `CPU::step` in a loop.
+pub fn run<M: Mapper>(cpu: &mut CPU<M>, end: u64) -> Result<(), Error> {
+    if !running(cpu) {
+        return cpu.step().map(|_| ());
+    }
+    let start = key(&cpu.registers);
+    let block = match cpu.bus.blocks.blocks.get(&start) {
+        Some(block) => block.clone(),
+        None => {
+            let (block, pages) = decode(cpu);
+            let block = Arc::new(block);
+            cpu.bus.blocks.insert(start, block.clone(), &pages);
+            block
+        }
+    };
+    if block.instructions.is_empty() {
+        return cpu.step().map(|_| ());
+    }
+    cpu.bus.blocks.invalidated = false;
+    for instruction in &block.instructions {
+        // Fetching an opcode from memory has no side effects other than
+        // spending time and setting the data bus.
+        let (pb, pc) = (cpu.registers.pb, cpu.registers.pc);
+        cpu.instruction = (pb, pc);
+        let cycles = cpu.bus.access_cycles(pb, pc);
+        cpu.spend(cycles);
+        cpu.bus.mdr = instruction.opcode;
+        cpu.registers.pc = pc.wrapping_add(1);
+        (instruction.handler)(cpu);
+        if let Some(error) = cpu.take_fault() {
+            return Err(error);
+        }
+        if cpu.cycles >= end || cpu.bus.blocks.invalidated || !running(cpu) ||
+           key(&cpu.registers) != instruction.next {
+            break;
+        }
+    }
+    Ok(())
+}
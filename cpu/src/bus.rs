@@ -0,0 +1,258 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! System bus, connecting the CPU to memory and other chips.
+//!
+//! The A-bus carries 24-bit addresses, and reaches work RAM, the
+//! cartridge, and registers inside of the CPU. The B-bus carries 8-bit
+//! addresses of registers of other chips, and is visible on the A-bus at
+//! $2100-$21FF in banks $00-$3F and $80-$BF.
+
+#[cfg(feature = "block-cache")]
+use blocks::Blocks;
+use dma::Dma;
+use error::Error;
+use io::Io;
+use mapper::Mapper;
+use ppu::Ppu;
+
+pub const WRAM_SIZE: usize = 0x2_0000;
+
+/// A chip on the B-bus, seeing accesses by the low byte of their address.
+pub trait Device {
+    /// Reads a register. Bits which the chip doesn't drive should be taken
+    /// from `open_bus`, the last value on the data bus.
+    fn read(&mut self, address: u8, open_bus: u8) -> u8;
+    fn write(&mut self, address: u8, value: u8);
+
+    /// Tells the chip the current scanline and dot, before it's accessed.
+    fn set_position(&mut self, _line: u64, _dot: u64) {}
+
+    /// Copies the last picture drawn by the chip, for chips which draw one.
+    fn draw_frame(&mut self, _buffer: &mut [u32]) {}
+}
+
+/// Stands in for a chip which isn't emulated. Reads return open bus, and
+/// writes are ignored.
+pub struct Disconnected;
+
+impl Device for Disconnected {
+    fn read(&mut self, _address: u8, open_bus: u8) -> u8 {
+        open_bus
+    }
+
+    fn write(&mut self, _address: u8, _value: u8) {}
+}
+
+pub struct Bus<M: Mapper> {
+    /// Work RAM, at $7E0000-$7FFFFF, with the first 8 KiB mirrored in
+    /// banks $00-$3F and $80-$BF.
+    pub wram: [u8; WRAM_SIZE],
+    pub mapper: M,
+    /// Picture processing unit, at $2100-$213F.
+    pub ppu: Box<dyn Device + Send>,
+    /// Communication ports of the audio processing unit, at $2140-$217F.
+    pub apu: Box<dyn Device + Send>,
+    pub io: Io,
+    pub dma: Dma,
+    /// Memory data register, the last value on the data bus. Reading an
+    /// address which nothing drives returns it.
+    pub mdr: u8,
+    /// WMADDL/M/H ($2181-$2183), 17-bit address in work RAM accessed
+    /// through WMDATA ($2180).
+    pub wram_address: u32,
+    /// Blocks of code decoded from memory, dropped when it's written to.
+    #[cfg(feature = "block-cache")]
+    pub(crate) blocks: Blocks<M>,
+}
+
+/// Index in work RAM of an address.
+pub fn wram_index(bank: u8, address: u16) -> Option<usize> {
+    match (bank, address) {
+        (0x00...0x3F, 0x0000...0x1FFF) |
+        (0x80...0xBF, 0x0000...0x1FFF) |
+        (0x7E, _) => Some(address as usize),
+        (0x7F, _) => Some(0x10000 + address as usize),
+        _ => None,
+    }
+}
+
+/// Whether a bank has I/O registers in $2000-$5FFF.
+fn system_bank(bank: u8) -> bool {
+    bank & 0x40 == 0
+}
+
+impl<M: Mapper> Bus<M> {
+    pub fn new(mapper: M) -> Self {
+        Bus {
+            wram: [0x55; WRAM_SIZE],
+            mapper,
+            ppu: Box::new(Ppu::new()),
+            apu: Box::new(Disconnected),
+            io: Io::new(),
+            dma: Dma::new(),
+            mdr: 0,
+            wram_address: 0,
+            #[cfg(feature = "block-cache")]
+            blocks: Blocks::new(),
+        }
+    }
+
+    /// Resets registers of the CPU. Memory and other chips are left
+    /// untouched.
+    pub fn reset(&mut self) {
+        self.io.reset();
+    }
+
+    /// Number of master cycles an access to a given address takes.
+    pub fn access_cycles(&self, bank: u8, address: u16) -> u64 {
+        match (bank, address) {
+            (0x40...0x7F, _) => 8,
+            (0x80...0xBF, 0x8000...0xFFFF) |
+            (0xC0...0xFF, _) => if self.io.fast_rom { 6 } else { 8 },
+            (_, 0x0000...0x1FFF) |
+            (_, 0x6000...0xFFFF) => 8,
+            (_, 0x4000...0x41FF) => 12,
+            _ => 6,
+        }
+    }
+
+    /// Reads work RAM or the cartridge, returning `None` for registers and
+    /// unmapped addresses.
+    pub fn memory(&self, bank: u8, address: u16) -> Option<u8> {
+        if let Some(index) = wram_index(bank, address) {
+            return Some(self.wram[index]);
+        }
+        if system_bank(bank) {
+            match address {
+                0x2100...0x21FF | 0x4016...0x4017 | 0x4200...0x421F | 0x4300...0x437F => {
+                    return None
+                }
+                _ => {}
+            }
+        }
+        self.mapper.read(bank, address)
+    }
+
+    /// Reads memory without side effects. Registers read as open bus.
+    pub fn peek(&self, bank: u8, address: u16) -> u8 {
+        self.memory(bank, address).unwrap_or(self.mdr)
+    }
+
+    pub fn read(&mut self, bank: u8, address: u16) -> u8 {
+        let open_bus = self.mdr;
+        let value = match address {
+            0x2100...0x21FF if system_bank(bank) => self.read_b(address as u8),
+            0x420B...0x420C | 0x4300...0x437F if system_bank(bank) => {
+                self.dma.read(address, open_bus)
+            }
+            0x4016...0x4017 | 0x4200...0x421F if system_bank(bank) => {
+                self.io.read(address, open_bus)
+            }
+            _ => self.peek(bank, address),
+        };
+        self.mdr = value;
+        value
+    }
+
+    pub fn write(&mut self, bank: u8, address: u16, value: u8) -> Result<(), Error> {
+        self.mdr = value;
+        if let Some(index) = wram_index(bank, address) {
+            self.write_wram(index, value);
+            return Ok(());
+        }
+        if system_bank(bank) {
+            match address {
+                0x2100...0x21FF => self.write_b(address as u8, value),
+                0x420B...0x420C | 0x4300...0x437F => self.dma.write(address, value),
+                0x4016...0x4017 | 0x4200...0x421F => self.io.write(address, value),
+                _ => return self.write_cartridge(bank, address, value),
+            }
+            return Ok(());
+        }
+        self.write_cartridge(bank, address, value)
+    }
+
+    fn write_wram(&mut self, index: usize, value: u8) {
+        self.wram[index] = value;
+        #[cfg(feature = "block-cache")]
+        self.blocks.invalidate((index >> 8) as u32);
+    }
+
+    fn write_cartridge(&mut self, bank: u8, address: u16, value: u8) -> Result<(), Error> {
+        #[cfg(feature = "block-cache")]
+        self.blocks.invalidate(page(bank, address));
+        self.mapper.write(bank, address, value)
+    }
+
+    /// Copies the last finished frame drawn by the PPU.
+    pub fn draw_frame(&mut self, buffer: &mut [u32]) {
+        let (line, dot) = self.io.position();
+        self.ppu.set_position(line, dot);
+        self.ppu.draw_frame(buffer);
+    }
+
+    /// Reads a register on the B-bus.
+    pub fn read_b(&mut self, address: u8) -> u8 {
+        match address {
+            0x00...0x3F => {
+                let (line, dot) = self.io.position();
+                self.ppu.set_position(line, dot);
+                self.ppu.read(address, self.mdr)
+            }
+            0x40...0x7F => self.apu.read(address, self.mdr),
+            // WMDATA
+            0x80 => {
+                let value = self.wram[self.wram_address as usize];
+                self.wram_address = (self.wram_address + 1) & 0x1FFFF;
+                value
+            }
+            _ => self.mdr,
+        }
+    }
+
+    /// Writes a register on the B-bus.
+    pub fn write_b(&mut self, address: u8, value: u8) {
+        match address {
+            0x00...0x3F => {
+                let (line, dot) = self.io.position();
+                self.ppu.set_position(line, dot);
+                self.ppu.write(address, value)
+            }
+            0x40...0x7F => self.apu.write(address, value),
+            // WMDATA, WMADDL, WMADDM, WMADDH
+            0x80 => {
+                let index = self.wram_address as usize;
+                self.write_wram(index, value);
+                self.wram_address = (self.wram_address + 1) & 0x1FFFF;
+            }
+            0x81 => self.wram_address = self.wram_address & 0x1FF00 | value as u32,
+            0x82 => self.wram_address = self.wram_address & 0x100FF | (value as u32) << 8,
+            0x83 => self.wram_address = self.wram_address & 0x0FFFF | (value as u32 & 1) << 16,
+            _ => {}
+        }
+    }
+}
+
+/// Identifies a 256 byte page of memory. Mirrors of the same memory share
+/// an identifier.
+#[cfg(feature = "block-cache")]
+pub fn page(bank: u8, address: u16) -> u32 {
+    match wram_index(bank, address) {
+        Some(index) => (index >> 8) as u32,
+        None => 0x200 + ((bank as u32) << 8 | (address >> 8) as u32),
+    }
+}
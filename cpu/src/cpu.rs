@@ -15,10 +15,14 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use bitwidth::BitWidth;
+#[cfg(feature = "block-cache")]
+use blocks;
+use bus::Bus;
+use dma;
+use error::{Error, ErrorPolicy};
 use instructions;
 use mapper::Mapper;
 
-const RAM_SIZE: usize = 0x2_0000;
 
 pub struct Registers {
     pub a: u16,
@@ -30,6 +34,54 @@ pub struct Registers {
     pub pc: u16,
     pub pb: u8,
     pub flags: Flags,
+    /// Whether the processor runs in 6502 emulation mode.
+    pub emulation: bool,
+}
+
+impl Registers {
+    /// Replaces the processor status register.
+    ///
+    /// Switching index registers to 8-bit mode clears high bytes of X and
+    /// Y, like the hardware does. In emulation mode registers are always
+    /// 8-bit.
+    pub fn set_flags(&mut self, mut flags: Flags) {
+        if self.emulation {
+            flags.remove(FLAG_A16 | FLAG_XY16);
+        }
+        self.flags = flags;
+        if !flags.contains(FLAG_XY16) {
+            self.x &= 0xFF;
+            self.y &= 0xFF;
+        }
+    }
+
+    /// Switches between native and emulation mode.
+    ///
+    /// Entering emulation mode makes all registers 8-bit and moves the
+    /// stack to page 1.
+    pub fn set_emulation(&mut self, emulation: bool) {
+        self.emulation = emulation;
+        if emulation {
+            self.sp = 0x100 | (self.sp & 0xFF);
+            let flags = self.flags;
+            self.set_flags(flags);
+        }
+    }
+
+    /// Processor status register as stored by PHP.
+    ///
+    /// `FLAG_A16` and `FLAG_XY16` are inverted compared to the hardware
+    /// where set M and X bits mean 8-bit registers.
+    pub fn status(&self) -> u8 {
+        self.flags.bits() ^ (FLAG_A16 | FLAG_XY16).bits()
+    }
+
+    /// Replaces the processor status register with a value in the format
+    /// used by PLP.
+    pub fn set_status(&mut self, status: u8) {
+        let flags = Flags::from_bits_truncate(status) ^ (FLAG_A16 | FLAG_XY16);
+        self.set_flags(flags);
+    }
 }
 
 bitflags! {
@@ -45,10 +97,65 @@ bitflags! {
     }
 }
 
+/// Interrupt sources, each with its own vector in bank 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    Cop,
+    Brk,
+    Abort,
+    Nmi,
+    Irq,
+    Reset,
+}
+
+impl Interrupt {
+    /// Address of the interrupt vector. Native and emulation mode use
+    /// separate vector tables, and in emulation mode BRK shares the IRQ
+    /// vector.
+    pub fn vector(self, emulation: bool) -> u16 {
+        match (self, emulation) {
+            (Interrupt::Cop, false) => 0xFFE4,
+            (Interrupt::Brk, false) => 0xFFE6,
+            (Interrupt::Abort, false) => 0xFFE8,
+            (Interrupt::Nmi, false) => 0xFFEA,
+            (Interrupt::Irq, false) => 0xFFEE,
+            (Interrupt::Cop, true) => 0xFFF4,
+            (Interrupt::Abort, true) => 0xFFF8,
+            (Interrupt::Nmi, true) => 0xFFFA,
+            (Interrupt::Reset, _) => 0xFFFC,
+            (Interrupt::Brk, true) | (Interrupt::Irq, true) => 0xFFFE,
+        }
+    }
+}
+
+/// Whether the processor is running instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Running,
+    /// Halted by WAI until the next NMI or IRQ.
+    Waiting,
+    /// Halted by STP until reset.
+    Stopped,
+}
+
 pub struct CPU<M: Mapper> {
     pub registers: Registers,
-    pub ram: [u8; RAM_SIZE],
-    pub mapper: M,
+    pub bus: Bus<M>,
+    /// Number of master cycles spent so far.
+    pub cycles: u64,
+    /// Pending NMI. NMI is edge triggered, so this is cleared once the
+    /// interrupt is serviced.
+    pub nmi: bool,
+    /// State of the IRQ line. IRQ is level triggered, the interrupt is
+    /// serviced as long as the line is set and `FLAG_NO_IRQ` is clear.
+    pub irq: bool,
+    pub state: State,
+    pub error_policy: ErrorPolicy,
+    /// First error reported during the current step.
+    fault: Option<Error>,
+    /// PB:PC at the start of the current instruction, reported along with
+    /// errors.
+    pub(crate) instruction: (u8, u16),
 }
 
 impl<M: Mapper> CPU<M> {
@@ -65,26 +172,59 @@ impl<M: Mapper> CPU<M> {
     /// ```
     pub fn new(mapper: M) -> Self {
         let mut cpu = CPU {
-            ram: [0x55; RAM_SIZE],
             registers: Registers {
                 a: 0,
                 x: 0,
                 y: 0,
                 dp: 0,
-                sp: 0xFF,
+                sp: 0x1FF,
                 db: 0,
                 pc: 0,
                 pb: 0,
                 flags: FLAG_NO_IRQ,
+                emulation: true,
             },
-            mapper: mapper,
+            bus: Bus::new(mapper),
+            cycles: 0,
+            nmi: false,
+            irq: false,
+            state: State::Running,
+            error_policy: ErrorPolicy::Stop,
+            fault: None,
+            instruction: (0, 0),
         };
-        // Reset vector
-        cpu.registers.pc = cpu.read(0x00, 0xFFFC);
+        cpu.reset();
         cpu
     }
 
-    pub fn read<T: BitWidth>(&self, bank: u8, address: u16) -> T {
+    /// Resets the processor and jumps to the reset vector.
+    ///
+    /// This switches to emulation mode, but unlike other interrupts doesn't
+    /// push anything on the stack. Memory is left untouched.
+    pub fn reset(&mut self) {
+        self.registers.dp = 0;
+        self.registers.db = 0;
+        self.registers.pb = 0;
+        self.registers.set_emulation(true);
+        let flags = (self.registers.flags | FLAG_NO_IRQ) - FLAG_DECIMAL;
+        self.registers.set_flags(flags);
+        self.nmi = false;
+        self.bus.reset();
+        self.state = State::Running;
+        self.registers.pc = self.read(0x00, Interrupt::Reset.vector(true));
+    }
+
+    /// Aborts the processor, running the ABORT handler.
+    pub fn abort(&mut self) {
+        instructions::interrupt(self, Interrupt::Abort);
+    }
+
+    /// Reads a byte without spending any cycles, for debugging purposes.
+    pub fn peek(&self, bank: u8, address: u16) -> u8 {
+        self.bus.peek(bank, address)
+    }
+
+    pub fn read<T: BitWidth>(&mut self, bank: u8, address: u16) -> T {
         T::read(self, bank, address)
     }
 
@@ -92,7 +232,102 @@ impl<M: Mapper> CPU<M> {
         T::write(self, bank, address, value);
     }
 
-    pub fn step(&mut self) {
-        instructions::run_instruction(self);
+    /// Reports an error according to the error policy.
+    pub(crate) fn fault(&mut self, error: Error) {
+        if self.error_policy == ErrorPolicy::Stop && self.fault.is_none() {
+            let (pb, pc) = self.instruction;
+            self.fault = Some(error.at(pb, pc));
+        }
+    }
+
+    #[cfg(feature = "block-cache")]
+    pub(crate) fn take_fault(&mut self) -> Option<Error> {
+        self.fault.take()
+    }
+
+    /// Spends a CPU cycle taking a given number of master cycles.
+    pub(crate) fn spend(&mut self, cycles: u64) {
+        self.cycles += cycles;
+        self.bus.io.tick(self.cycles);
+        if self.cycles >= self.bus.dma.next_hdma() {
+            dma::hdma(self);
+        }
+    }
+
+    /// State of the IRQ line, driven either externally or by the timer.
+    pub(crate) fn irq_line(&self) -> bool {
+        self.irq || self.bus.io.timeup
+    }
+
+    /// Runs a single instruction, or services a pending interrupt.
+    ///
+    /// Returns a state of the processor after the step, or an error if
+    /// the instruction did something unsupported. When halted, steps
+    /// do nothing until the processor is woken up. An IRQ wakes up WAI
+    /// even when `FLAG_NO_IRQ` is set, in which case execution continues
+    /// with the next instruction without running the interrupt handler.
+    pub fn step(&mut self) -> Result<State, Error> {
+        if self.bus.io.nmi {
+            self.bus.io.nmi = false;
+            self.nmi = true;
+        }
+        match self.state {
+            State::Running => {}
+            State::Waiting if self.nmi || self.irq_line() => self.state = State::Running,
+            State::Waiting | State::Stopped => return Ok(self.state),
+        }
+        self.instruction = (self.registers.pb, self.registers.pc);
+        if self.nmi {
+            self.nmi = false;
+            instructions::interrupt(self, Interrupt::Nmi);
+        } else if self.irq_line() && !self.registers.flags.contains(FLAG_NO_IRQ) {
+            instructions::interrupt(self, Interrupt::Irq);
+        } else {
+            instructions::run_instruction(self);
+        }
+        match self.fault.take() {
+            Some(error) => Err(error),
+            None => Ok(self.state),
+        }
+    }
+
+    /// Runs until `cycles` reaches `end`, and returns the state of the
+    /// processor at that point.
+    ///
+    /// While running this is the same as calling `step` in a loop, but
+    /// with the `block-cache` feature it runs cached blocks of decoded
+    /// instructions. Time passes while halted, so an interrupt can wake
+    /// up the processor from WAI.
+    pub fn run_until(&mut self, end: u64) -> Result<State, Error> {
+        while self.cycles < end {
+            match self.state {
+                #[cfg(feature = "block-cache")]
+                State::Running => blocks::run(self, end)?,
+                #[cfg(not(feature = "block-cache"))]
+                State::Running => {
+                    self.step()?;
+                }
+                State::Waiting => {
+                    if !self.nmi && !self.bus.io.nmi && !self.irq_line() {
+                        self.skip_to_event(end);
+                    }
+                    self.step()?;
+                }
+                // Only a reset starts the processor again, but the I/O
+                // registers and HDMA keep running
+                State::Stopped => self.skip_to_event(end),
+            }
+        }
+        Ok(self.state)
+    }
+
+    /// Spends time while halted. Nothing happens until the next event of
+    /// the I/O registers, which may raise an interrupt, or until HDMA
+    /// runs.
+    fn skip_to_event(&mut self, end: u64) {
+        let next = self.bus.io.next_event().min(self.bus.dma.next_hdma());
+        let next = next.max(self.cycles).min(end);
+        let cycles = next - self.cycles;
+        self.spend(cycles);
     }
 }
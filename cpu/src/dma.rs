@@ -0,0 +1,416 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Direct memory access, with channel registers at $4300-$437F.
+//!
+//! Every channel copies data between the A-bus and a register on the
+//! B-bus, while the CPU waits for it to finish. Channels either run
+//! general purpose DMA started through MDMAEN, or HDMA enabled through
+//! HDMAEN, which copies a few bytes at the end of every visible scanline,
+//! following a table set up at the start of each frame.
+
+use bus;
+use cpu::CPU;
+use io::{CYCLES_PER_FRAME, CYCLES_PER_LINE, VBLANK_LINE};
+use mapper::Mapper;
+
+/// Offsets from BBADx of B-bus registers accessed in a single unit of
+/// transfer, by the transfer pattern in the low 3 bits of DMAPx.
+const PATTERNS: [&[u8]; 8] = [&[0], &[0, 1], &[0, 0], &[0, 0, 1, 1], &[0, 1, 2, 3], &[0, 1, 0, 1],
+                              &[0, 0], &[0, 0, 1, 1]];
+
+/// Bits of DMAPx.
+const B_TO_A: u8 = 0x80;
+const FIXED: u8 = 0x08;
+const DECREMENT: u8 = 0x10;
+const INDIRECT: u8 = 0x40;
+
+/// Bit of NTRLx, transferring data on every line rather than only the
+/// first one.
+const REPEAT: u8 = 0x80;
+
+/// HDMA sets up tables at this dot of the first scanline.
+const HDMA_INIT_DOT: u64 = 6;
+
+/// HDMA transfers data at this dot of every visible scanline.
+const HDMA_DOT: u64 = 278;
+
+/// Registers of a single DMA channel, at $43x0-$43xF.
+#[derive(Clone, Copy, Debug)]
+pub struct Channel {
+    /// DMAPx ($43x0), transfer parameters.
+    pub control: u8,
+    /// BBADx ($43x1), B-bus address.
+    pub b_address: u8,
+    /// A1TxL/H ($43x2-$43x3), A-bus address.
+    pub a_address: u16,
+    /// A1Bx ($43x4), A-bus bank.
+    pub a_bank: u8,
+    /// DASxL/H ($43x5-$43x6), byte count for DMA, indirect address for HDMA.
+    pub count: u16,
+    /// DASBx ($43x7), indirect HDMA bank.
+    pub indirect_bank: u8,
+    /// A2AxL/H ($43x8-$43x9), HDMA table address.
+    pub table_address: u16,
+    /// NTRLx ($43xA), HDMA line counter.
+    pub line_counter: u8,
+    /// $43xB and $43xF, which can hold any value.
+    pub unused: u8,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Channel {
+            control: 0xFF,
+            b_address: 0xFF,
+            a_address: 0xFFFF,
+            a_bank: 0xFF,
+            count: 0xFFFF,
+            indirect_bank: 0xFF,
+            table_address: 0xFFFF,
+            line_counter: 0xFF,
+            unused: 0xFF,
+        }
+    }
+}
+
+pub struct Dma {
+    pub channels: [Channel; 8],
+    /// MDMAEN ($420B), channels which start a transfer after the current
+    /// CPU cycle.
+    pub mdmaen: u8,
+    /// HDMAEN ($420C), channels which run HDMA.
+    pub hdmaen: u8,
+    /// Channels which reached the end of their HDMA table in this frame.
+    hdma_done: u8,
+    /// Channels which transfer data on the next HDMA scanline.
+    hdma_transfer: u8,
+    /// Channel running general purpose DMA, which stops when HDMA starts
+    /// on the same channel.
+    general: u8,
+    /// Master cycle at which HDMA runs next.
+    next_hdma: u64,
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        Dma::new()
+    }
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Dma {
+            channels: [Channel::new(); 8],
+            mdmaen: 0,
+            hdmaen: 0,
+            hdma_done: 0,
+            hdma_transfer: 0,
+            general: 0,
+            next_hdma: HDMA_INIT_DOT * 4,
+        }
+    }
+
+    /// Master cycle at which HDMA runs next.
+    pub fn next_hdma(&self) -> u64 {
+        self.next_hdma
+    }
+
+    /// Reads a register, either MDMAEN, HDMAEN or one between $4300 and
+    /// $437F.
+    pub fn read(&mut self, address: u16, open_bus: u8) -> u8 {
+        if address < 0x4300 {
+            return open_bus;
+        }
+        let channel = &self.channels[(address >> 4 & 7) as usize];
+        match address & 0xF {
+            0x0 => channel.control,
+            0x1 => channel.b_address,
+            0x2 => channel.a_address as u8,
+            0x3 => (channel.a_address >> 8) as u8,
+            0x4 => channel.a_bank,
+            0x5 => channel.count as u8,
+            0x6 => (channel.count >> 8) as u8,
+            0x7 => channel.indirect_bank,
+            0x8 => channel.table_address as u8,
+            0x9 => (channel.table_address >> 8) as u8,
+            0xA => channel.line_counter,
+            0xB | 0xF => channel.unused,
+            _ => open_bus,
+        }
+    }
+
+    /// Writes a register, either MDMAEN, HDMAEN or one between $4300 and
+    /// $437F.
+    pub fn write(&mut self, address: u16, value: u8) {
+        if address == 0x420B {
+            self.mdmaen = value;
+            return;
+        }
+        if address == 0x420C {
+            self.hdmaen = value;
+            return;
+        }
+        let channel = &mut self.channels[(address >> 4 & 7) as usize];
+        match address & 0xF {
+            0x0 => channel.control = value,
+            0x1 => channel.b_address = value,
+            0x2 => set_low(&mut channel.a_address, value),
+            0x3 => set_high(&mut channel.a_address, value),
+            0x4 => channel.a_bank = value,
+            0x5 => set_low(&mut channel.count, value),
+            0x6 => set_high(&mut channel.count, value),
+            0x7 => channel.indirect_bank = value,
+            0x8 => set_low(&mut channel.table_address, value),
+            0x9 => set_high(&mut channel.table_address, value),
+            0xA => channel.line_counter = value,
+            0xB | 0xF => channel.unused = value,
+            _ => {}
+        }
+    }
+}
+
+fn set_low(word: &mut u16, value: u8) {
+    *word = *word & 0xFF00 | value as u16;
+}
+
+fn set_high(word: &mut u16, value: u8) {
+    *word = *word & 0x00FF | (value as u16) << 8;
+}
+
+/// Whether DMA can reach an A-bus address. Registers are out of its reach.
+fn valid_a(bank: u8, address: u16) -> bool {
+    bank & 0x40 != 0 ||
+    !matches!(address, 0x2100...0x21FF | 0x4000...0x41FF | 0x4200...0x421F | 0x4300...0x437F)
+}
+
+/// Copies a byte between the A-bus and the B-bus, in 8 master cycles.
+fn transfer_byte<M: Mapper>(cpu: &mut CPU<M>, control: u8, bank: u8, address: u16, b_address: u8) {
+    let valid_a = valid_a(bank, address);
+    // Work RAM can't be copied to itself through WMDATA
+    let valid_b = b_address != 0x80 || bus::wram_index(bank, address).is_none();
+    if control & B_TO_A == 0 {
+        let value = if valid_a {
+            cpu.bus.read(bank, address)
+        } else {
+            cpu.bus.mdr
+        };
+        if valid_b {
+            cpu.bus.write_b(b_address, value);
+        }
+    } else {
+        let value = if valid_b {
+            cpu.bus.read_b(b_address)
+        } else {
+            cpu.bus.mdr
+        };
+        cpu.bus.mdr = value;
+        if valid_a {
+            if let Err(error) = cpu.bus.write(bank, address, value) {
+                cpu.fault(error);
+            }
+        }
+    }
+    cpu.spend(8);
+}
+
+/// Runs general purpose DMA on channels enabled in MDMAEN, in order of
+/// their numbers.
+pub fn run<M: Mapper>(cpu: &mut CPU<M>) {
+    let channels = cpu.bus.dma.mdmaen;
+    cpu.bus.dma.mdmaen = 0;
+    // Transfers start on a multiple of 8 master cycles, after 8 cycles of
+    // overhead, and every channel adds another 8 cycles.
+    let misalignment = cpu.cycles % 8;
+    cpu.spend(8 + (8 - misalignment) % 8);
+    for index in 0..8 {
+        if channels & 1 << index == 0 {
+            continue;
+        }
+        cpu.bus.dma.general = 1 << index;
+        cpu.spend(8);
+        let pattern = PATTERNS[(cpu.bus.dma.channels[index].control & 7) as usize];
+        let mut unit = 0;
+        loop {
+            let channel = cpu.bus.dma.channels[index];
+            let b_address = channel.b_address.wrapping_add(pattern[unit % pattern.len()]);
+            transfer_byte(cpu,
+                          channel.control,
+                          channel.a_bank,
+                          channel.a_address,
+                          b_address);
+            let channel = &mut cpu.bus.dma.channels[index];
+            if channel.control & FIXED == 0 {
+                channel.a_address = if channel.control & DECREMENT == 0 {
+                    channel.a_address.wrapping_add(1)
+                } else {
+                    channel.a_address.wrapping_sub(1)
+                };
+            }
+            // A count of 0 transfers 65536 bytes. HDMA pauses the
+            // transfer while it runs, and cancels it when it uses the same
+            // channel.
+            channel.count = channel.count.wrapping_sub(1);
+            if channel.count == 0 || cpu.bus.dma.general == 0 {
+                break;
+            }
+            unit += 1;
+        }
+    }
+    cpu.bus.dma.general = 0;
+}
+
+/// First time HDMA runs after a given master cycle.
+fn next_event(after: u64) -> u64 {
+    let frame = after - after % CYCLES_PER_FRAME;
+    let init = frame + HDMA_INIT_DOT * 4;
+    if after < init {
+        return init;
+    }
+    for line in (after - frame) / CYCLES_PER_LINE..VBLANK_LINE {
+        let transfer = frame + line * CYCLES_PER_LINE + HDMA_DOT * 4;
+        if transfer > after {
+            return transfer;
+        }
+    }
+    init + CYCLES_PER_FRAME
+}
+
+/// Runs HDMA if it's due, pausing the CPU and general purpose DMA.
+pub fn hdma<M: Mapper>(cpu: &mut CPU<M>) {
+    while cpu.cycles >= cpu.bus.dma.next_hdma {
+        let event = cpu.bus.dma.next_hdma;
+        cpu.bus.dma.next_hdma = next_event(event);
+        if event % CYCLES_PER_FRAME == HDMA_INIT_DOT * 4 {
+            hdma_init(cpu);
+        } else {
+            hdma_line(cpu);
+        }
+    }
+}
+
+/// Reads the next byte of the HDMA table of a channel, in 8 master cycles.
+fn read_table<M: Mapper>(cpu: &mut CPU<M>, index: usize) -> u8 {
+    let (bank, address) = {
+        let channel = &mut cpu.bus.dma.channels[index];
+        let address = channel.table_address;
+        channel.table_address = address.wrapping_add(1);
+        (channel.a_bank, address)
+    };
+    let value = if valid_a(bank, address) {
+        cpu.bus.read(bank, address)
+    } else {
+        cpu.bus.mdr
+    };
+    cpu.spend(8);
+    value
+}
+
+/// Loads the next entry of the HDMA table of a channel: the line counter,
+/// followed by the data address in indirect mode. A line counter of 0
+/// ends the table.
+fn load_entry<M: Mapper>(cpu: &mut CPU<M>, index: usize) {
+    let line_counter = read_table(cpu, index);
+    cpu.bus.dma.channels[index].line_counter = line_counter;
+    if line_counter == 0 {
+        cpu.bus.dma.hdma_done |= 1 << index;
+        return;
+    }
+    if cpu.bus.dma.channels[index].control & INDIRECT != 0 {
+        let low = read_table(cpu, index);
+        let high = read_table(cpu, index);
+        cpu.bus.dma.channels[index].count = (high as u16) << 8 | low as u16;
+    }
+    cpu.bus.dma.hdma_transfer |= 1 << index;
+}
+
+/// Starts HDMA tables of enabled channels from A1Tx, at the start of
+/// a frame.
+fn hdma_init<M: Mapper>(cpu: &mut CPU<M>) {
+    cpu.bus.dma.hdma_done = 0;
+    cpu.bus.dma.hdma_transfer = 0;
+    let channels = cpu.bus.dma.hdmaen;
+    if channels == 0 {
+        return;
+    }
+    cpu.spend(18);
+    for index in 0..8 {
+        if channels & 1 << index == 0 {
+            continue;
+        }
+        cpu.bus.dma.general &= !(1 << index);
+        let channel = &mut cpu.bus.dma.channels[index];
+        channel.table_address = channel.a_address;
+        load_entry(cpu, index);
+    }
+}
+
+/// Transfers a unit of data for every active HDMA channel, then counts
+/// down their line counters, at the end of a visible scanline.
+fn hdma_line<M: Mapper>(cpu: &mut CPU<M>) {
+    let channels = cpu.bus.dma.hdmaen & !cpu.bus.dma.hdma_done;
+    if channels == 0 {
+        return;
+    }
+    cpu.spend(18);
+    for index in 0..8 {
+        if channels & 1 << index == 0 {
+            continue;
+        }
+        cpu.bus.dma.general &= !(1 << index);
+        cpu.spend(8);
+        if cpu.bus.dma.hdma_transfer & 1 << index == 0 {
+            continue;
+        }
+        let (control, b_address) = {
+            let channel = &cpu.bus.dma.channels[index];
+            (channel.control, channel.b_address)
+        };
+        for &offset in PATTERNS[(control & 7) as usize] {
+            let channel = &mut cpu.bus.dma.channels[index];
+            // Data follows the table, or is read through the indirect
+            // address
+            let (bank, address) = if control & INDIRECT == 0 {
+                let address = channel.table_address;
+                channel.table_address = address.wrapping_add(1);
+                (channel.a_bank, address)
+            } else {
+                let address = channel.count;
+                channel.count = address.wrapping_add(1);
+                (channel.indirect_bank, address)
+            };
+            transfer_byte(cpu, control, bank, address, b_address.wrapping_add(offset));
+        }
+    }
+    for index in 0..8 {
+        if channels & 1 << index == 0 {
+            continue;
+        }
+        let line_counter = {
+            let channel = &mut cpu.bus.dma.channels[index];
+            channel.line_counter = channel.line_counter.wrapping_sub(1);
+            channel.line_counter
+        };
+        if line_counter & REPEAT == 0 {
+            cpu.bus.dma.hdma_transfer &= !(1 << index);
+        } else {
+            cpu.bus.dma.hdma_transfer |= 1 << index;
+        }
+        if line_counter & !REPEAT == 0 {
+            load_entry(cpu, index);
+        }
+    }
+}
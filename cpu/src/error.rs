@@ -0,0 +1,88 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::error;
+use std::fmt;
+
+/// An error that stopped emulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Tried to write to an address which doesn't have anything to write
+    /// to, while running the instruction at `pb`:`pc`.
+    UnmappedWrite {
+        pb: u8,
+        pc: u16,
+        bank: u8,
+        address: u16,
+        value: u8,
+    },
+}
+
+impl Error {
+    /// Creates an error for a write to an unmapped address. The CPU fills
+    /// in the address of its instruction when reporting it.
+    pub fn unmapped_write(bank: u8, address: u16, value: u8) -> Error {
+        Error::UnmappedWrite {
+            pb: 0,
+            pc: 0,
+            bank,
+            address,
+            value,
+        }
+    }
+
+    /// Sets the address of the instruction which caused the error.
+    pub(crate) fn at(self, pb: u8, pc: u16) -> Error {
+        match self {
+            Error::UnmappedWrite { bank, address, value, .. } => {
+                Error::UnmappedWrite {
+                    pb,
+                    pc,
+                    bank,
+                    address,
+                    value,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnmappedWrite { pb, pc, bank, address, value } => {
+                write!(f,
+                       "write of {:02x} to unmapped address {:02x}:{:04x} at {:02x}:{:04x}",
+                       value,
+                       bank,
+                       address,
+                       pb,
+                       pc)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// What to do when an error happens while running an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Return the error from `CPU::step`, after the instruction finishes.
+    Stop,
+    /// Ignore the error. Unmapped writes are dropped.
+    Skip,
+}
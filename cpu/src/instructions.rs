@@ -15,17 +15,252 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use bitwidth::BitWidth;
-use cpu::{CPU, Flags, FLAG_NO_IRQ, FLAG_A16};
+use cpu::{CPU, Interrupt, Registers, State, Flags, FLAG_CARRY, FLAG_ZERO, FLAG_NO_IRQ,
+          FLAG_DECIMAL, FLAG_XY16, FLAG_A16, FLAG_OVERFLOW, FLAG_NEGATIVE};
 use mapper::Mapper;
+use opcodes::{AddressingMode, Instruction, Width};
 
 fn fetch<M: Mapper>(cpu: &mut CPU<M>) -> u8 {
-    let byte = cpu.read(cpu.registers.pb, cpu.registers.pc);
+    let (pb, pc) = (cpu.registers.pb, cpu.registers.pc);
+    let byte = cpu.read(pb, pc);
     cpu.registers.pc = cpu.registers.pc.wrapping_add(1);
     byte
 }
 
+fn fetch_word<M: Mapper>(cpu: &mut CPU<M>) -> u16 {
+    let a = fetch(cpu);
+    let b = fetch(cpu);
+    a as u16 | ((b as u16) << 8)
+}
+
 fn set_flag<M: Mapper>(cpu: &mut CPU<M>, flags: Flags) {
-    cpu.registers.flags |= flags;
+    idle(cpu);
+    let flags = cpu.registers.flags | flags;
+    cpu.registers.set_flags(flags);
+}
+
+fn clear_flag<M: Mapper>(cpu: &mut CPU<M>, flags: Flags) {
+    idle(cpu);
+    let flags = cpu.registers.flags - flags;
+    cpu.registers.set_flags(flags);
+}
+
+/// Sets zero and negative flags according to a value. The width of a value
+/// decides which bit is treated as a sign bit.
+fn set_zn<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let value = value.into();
+    cpu.registers.flags.set(FLAG_ZERO, value == 0);
+    cpu.registers.flags.set(FLAG_NEGATIVE, value >> (T::BITS - 1) != 0);
+}
+
+/// Spends an internal operation cycle.
+fn idle<M: Mapper>(cpu: &mut CPU<M>) {
+    cpu.spend(6);
+}
+
+// Stack
+//
+// In emulation mode the stack pointer stays in page 1. Instructions
+// introduced by 65816 are an exception, those can access the stack outside
+// of page 1, and only after they finish the high byte of stack pointer is
+// set back to 1. Those use `push_native` and `pull_native`, followed by
+// `end_native_stack`.
+
+fn push_byte<M: Mapper>(cpu: &mut CPU<M>, value: u8, native: bool) {
+    let sp = cpu.registers.sp;
+    cpu.write(0, sp, value);
+    cpu.registers.sp = if cpu.registers.emulation && !native {
+        0x100 | (sp.wrapping_sub(1) & 0xFF)
+    } else {
+        sp.wrapping_sub(1)
+    };
+}
+
+fn pull_byte<M: Mapper>(cpu: &mut CPU<M>, native: bool) -> u8 {
+    let sp = if cpu.registers.emulation && !native {
+        0x100 | (cpu.registers.sp.wrapping_add(1) & 0xFF)
+    } else {
+        cpu.registers.sp.wrapping_add(1)
+    };
+    cpu.registers.sp = sp;
+    cpu.read(0, sp)
+}
+
+fn push_value<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T, native: bool) {
+    let value = value.into();
+    if T::BITS == 16 {
+        push_byte(cpu, (value >> 8) as u8, native);
+    }
+    push_byte(cpu, value as u8, native);
+}
+
+fn pull_value<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, native: bool) -> T {
+    let mut value = pull_byte(cpu, native) as u16;
+    if T::BITS == 16 {
+        value |= (pull_byte(cpu, native) as u16) << 8;
+    }
+    T::get(value)
+}
+
+/// Pushes a value on the stack, high byte first.
+fn push<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    push_value(cpu, value, false);
+}
+
+/// Pulls a value from the stack, low byte first.
+fn pull<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) -> T {
+    pull_value(cpu, false)
+}
+
+fn push_native<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    push_value(cpu, value, true);
+}
+
+fn pull_native<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) -> T {
+    pull_value(cpu, true)
+}
+
+fn end_native_stack<M: Mapper>(cpu: &mut CPU<M>) {
+    if cpu.registers.emulation {
+        cpu.registers.sp = 0x100 | (cpu.registers.sp & 0xFF);
+    }
+}
+
+/// How multi-byte accesses behave when they reach the end of a page or bank.
+#[derive(Clone, Copy, PartialEq)]
+enum Wrapping {
+    /// Accesses continue into the next bank.
+    None,
+    /// Accesses wrap around within a bank. Direct page and stack accesses
+    /// are always within bank 0.
+    Bank,
+    /// Accesses wrap around within a page. This happens for direct page
+    /// accesses in emulation mode.
+    Page,
+}
+
+/// Effective address of an operand.
+#[derive(Clone, Copy)]
+struct Address {
+    bank: u8,
+    address: u16,
+    wrapping: Wrapping,
+}
+
+impl Address {
+    fn new(bank: u8, address: u16) -> Address {
+        Address {
+            bank,
+            address,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    fn bank_zero(address: u16) -> Address {
+        Address {
+            bank: 0,
+            address,
+            wrapping: Wrapping::Bank,
+        }
+    }
+
+    /// Adds an index to an address. Indexing can cross into the next bank.
+    fn index(self, by: u16) -> Address {
+        let full = ((self.bank as u32) << 16 | self.address as u32) + by as u32;
+        Address::new((full >> 16) as u8, full as u16)
+    }
+
+    /// Address of a byte following this one in a multi-byte access.
+    fn offset(self, by: u16) -> Address {
+        let address = match self.wrapping {
+            Wrapping::None => return self.index(by),
+            Wrapping::Bank => self.address.wrapping_add(by),
+            Wrapping::Page => (self.address & 0xFF00) | (self.address.wrapping_add(by) & 0xFF),
+        };
+        Address { address, ..self }
+    }
+
+    fn read<M: Mapper, T: BitWidth>(self, cpu: &mut CPU<M>) -> T {
+        match self.wrapping {
+            Wrapping::None => T::read_long(cpu, self.bank, self.address),
+            Wrapping::Bank => cpu.read(self.bank, self.address),
+            Wrapping::Page => {
+                let mut value = cpu.read::<u8>(self.bank, self.address) as u16;
+                if T::BITS == 16 {
+                    let next = self.offset(1);
+                    value |= (cpu.read::<u8>(next.bank, next.address) as u16) << 8;
+                }
+                T::get(value)
+            }
+        }
+    }
+
+    fn write<M: Mapper, T: BitWidth>(self, cpu: &mut CPU<M>, value: T) {
+        match self.wrapping {
+            Wrapping::None => T::write_long(cpu, self.bank, self.address, value),
+            Wrapping::Bank => cpu.write(self.bank, self.address, value),
+            Wrapping::Page => {
+                let value = value.into();
+                cpu.write(self.bank, self.address, value as u8);
+                if T::BITS == 16 {
+                    let next = self.offset(1);
+                    cpu.write(next.bank, next.address, (value >> 8) as u8);
+                }
+            }
+        }
+    }
+}
+
+fn read_pointer<M: Mapper>(cpu: &mut CPU<M>, bank: u8, location: Address) -> Address {
+    Address::new(bank, location.read(cpu))
+}
+
+fn read_long_pointer<M: Mapper>(cpu: &mut CPU<M>, location: Address) -> Address {
+    let address = location.read(cpu);
+    let bank = location.offset(2).read(cpu);
+    Address::new(bank, address)
+}
+
+/// Fetches a direct page offset.
+///
+/// Direct page accesses take an extra cycle when the low byte of the
+/// direct page register is not zero.
+fn fetch_direct_offset<M: Mapper>(cpu: &mut CPU<M>) -> u16 {
+    let offset = fetch(cpu) as u16;
+    if cpu.registers.dp & 0xFF != 0 {
+        idle(cpu);
+    }
+    offset
+}
+
+/// Address within the direct page.
+///
+/// In emulation mode, when the direct page is aligned to a page, indexing
+/// and pointer reads stay within that page. Instructions introduced by
+/// 65816 use `Address::bank_zero` directly instead.
+fn direct_page<M: Mapper>(cpu: &CPU<M>, offset: u16) -> Address {
+    let dp = cpu.registers.dp;
+    if cpu.registers.emulation && dp & 0xFF == 0 {
+        Address {
+            bank: 0,
+            address: dp | (offset & 0xFF),
+            wrapping: Wrapping::Page,
+        }
+    } else {
+        Address::bank_zero(dp.wrapping_add(offset))
+    }
+}
+
+/// Indexes a data address. Reads take an extra cycle when the index
+/// registers are 16-bit or when indexing crosses a page, while writes always
+/// take an extra cycle.
+fn index_data<M: Mapper>(cpu: &mut CPU<M>, base: Address, by: u16, write: bool) -> Address {
+    let address = base.index(by);
+    if write || cpu.registers.flags.contains(FLAG_XY16) ||
+       base.address & 0xFF00 != address.address & 0xFF00 {
+        idle(cpu);
+    }
+    address
 }
 
 // Addressing types
@@ -38,54 +273,278 @@ fn set_flag<M: Mapper>(cpu: &mut CPU<M>, flags: Flags) {
 // Rust doesn't currently support higher-kinded types, and function literal
 // can be only resolved to a single type. To resolve this issue, a function
 // is passed twice, so Rust has to resolve types twice.
+//
+// Most addressing modes only differ in how an effective address is
+// computed, so `addressing_mode!` generates two functions from such a
+// computation: `name_address` which passes an address to an opcode
+// (for stores and read-modify-write instructions), and `name` which reads
+// a value from that address.
 
-fn absolute<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+fn address_reader<M, T, F>(cpu: &mut CPU<M>, address: Address, f: F)
+    where M: Mapper,
+          T: BitWidth,
+          F: FnOnce(&mut CPU<M>, T)
+{
+    let value = address.read(cpu);
+    f(cpu, value);
+}
+
+macro_rules! addressing_mode {
+    ($name:ident, $name_address:ident, |$cpu:ident, $write:ident| $resolve:block) => {
+        fn $name_address<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+            where M: Mapper,
+                  F: FnOnce(&mut CPU<M>, Address),
+                  G: FnOnce(&mut CPU<M>, Address)
+        {
+            let address = {
+                let $cpu: &mut CPU<M> = cpu;
+                let $write = true;
+                $resolve
+            };
+            if sixteen_bits {
+                g(cpu, address);
+            } else {
+                f(cpu, address);
+            }
+        }
+
+        fn $name<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+            where M: Mapper,
+                  F: FnOnce(&mut CPU<M>, u8),
+                  G: FnOnce(&mut CPU<M>, u16)
+        {
+            let address = {
+                let $cpu: &mut CPU<M> = cpu;
+                let $write = false;
+                $resolve
+            };
+            if sixteen_bits {
+                address_reader(cpu, address, g);
+            } else {
+                address_reader(cpu, address, f);
+            }
+        }
+    }
+}
+
+// addr
+addressing_mode!(absolute, absolute_address, |cpu, _write| {
+    let address = fetch_word(cpu);
+    Address::new(cpu.registers.db, address)
+});
+
+// addr,X
+addressing_mode!(absolute_x, absolute_x_address, |cpu, write| {
+    let address = fetch_word(cpu);
+    let x = cpu.registers.x;
+    index_data(cpu, Address::new(cpu.registers.db, address), x, write)
+});
+
+// addr,Y
+addressing_mode!(absolute_y, absolute_y_address, |cpu, write| {
+    let address = fetch_word(cpu);
+    let y = cpu.registers.y;
+    index_data(cpu, Address::new(cpu.registers.db, address), y, write)
+});
+
+// long
+addressing_mode!(absolute_long, absolute_long_address, |cpu, _write| {
+    let address = fetch_word(cpu);
+    let bank = fetch(cpu);
+    Address::new(bank, address)
+});
+
+// long,X
+addressing_mode!(absolute_long_x, absolute_long_x_address, |cpu, _write| {
+    let address = fetch_word(cpu);
+    let bank = fetch(cpu);
+    Address::new(bank, address).index(cpu.registers.x)
+});
+
+// dp
+addressing_mode!(direct, direct_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    direct_page(cpu, offset)
+});
+
+// dp,X
+addressing_mode!(direct_x, direct_x_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    idle(cpu);
+    direct_page(cpu, offset.wrapping_add(cpu.registers.x))
+});
+
+// dp,Y
+addressing_mode!(direct_y, direct_y_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    idle(cpu);
+    direct_page(cpu, offset.wrapping_add(cpu.registers.y))
+});
+
+// (dp)
+addressing_mode!(direct_indirect, direct_indirect_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    let pointer = direct_page(cpu, offset);
+    let db = cpu.registers.db;
+    read_pointer(cpu, db, pointer)
+});
+
+// [dp]
+addressing_mode!(direct_indirect_long, direct_indirect_long_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    let pointer = Address::bank_zero(cpu.registers.dp.wrapping_add(offset));
+    read_long_pointer(cpu, pointer)
+});
+
+// (dp,X)
+addressing_mode!(direct_x_indirect, direct_x_indirect_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    idle(cpu);
+    let pointer = direct_page(cpu, offset.wrapping_add(cpu.registers.x));
+    let db = cpu.registers.db;
+    read_pointer(cpu, db, pointer)
+});
+
+// (dp),Y
+addressing_mode!(direct_indirect_y, direct_indirect_y_address, |cpu, write| {
+    let offset = fetch_direct_offset(cpu);
+    let pointer = direct_page(cpu, offset);
+    let db = cpu.registers.db;
+    let base = read_pointer(cpu, db, pointer);
+    let y = cpu.registers.y;
+    index_data(cpu, base, y, write)
+});
+
+// [dp],Y
+addressing_mode!(direct_indirect_long_y, direct_indirect_long_y_address, |cpu, _write| {
+    let offset = fetch_direct_offset(cpu);
+    let pointer = Address::bank_zero(cpu.registers.dp.wrapping_add(offset));
+    read_long_pointer(cpu, pointer).index(cpu.registers.y)
+});
+
+// sr,S
+addressing_mode!(stack_relative, stack_relative_address, |cpu, _write| {
+    let offset = fetch(cpu) as u16;
+    idle(cpu);
+    Address::bank_zero(cpu.registers.sp.wrapping_add(offset))
+});
+
+// (sr,S),Y
+addressing_mode!(stack_relative_indirect_y, stack_relative_indirect_y_address, |cpu, _write| {
+    let offset = fetch(cpu) as u16;
+    idle(cpu);
+    let pointer = Address::bank_zero(cpu.registers.sp.wrapping_add(offset));
+    let db = cpu.registers.db;
+    let address = read_pointer(cpu, db, pointer).index(cpu.registers.y);
+    idle(cpu);
+    address
+});
+
+// Jump addressing modes. Those only compute a new program counter.
+
+// (addr)
+fn absolute_indirect<M: Mapper>(cpu: &mut CPU<M>) -> u16 {
+    let pointer = fetch_word(cpu);
+    Address::bank_zero(pointer).read(cpu)
+}
+
+// (addr,X)
+fn absolute_x_indirect<M: Mapper>(cpu: &mut CPU<M>) -> u16 {
+    let pointer = fetch_word(cpu).wrapping_add(cpu.registers.x);
+    idle(cpu);
+    let pointer = Address {
+        bank: cpu.registers.pb,
+        address: pointer,
+        wrapping: Wrapping::Bank,
+    };
+    pointer.read(cpu)
+}
+
+// [addr]
+fn absolute_indirect_long<M: Mapper>(cpu: &mut CPU<M>) -> Address {
+    let pointer = fetch_word(cpu);
+    read_long_pointer(cpu, Address::bank_zero(pointer))
+}
+
+fn immediate<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
     where M: Mapper,
           F: FnOnce(&mut CPU<M>, u8),
           G: FnOnce(&mut CPU<M>, u16)
 {
-    fn address_reader<M, T, F>(cpu: &mut CPU<M>, address: u16, f: F)
-        where M: Mapper,
-              T: BitWidth,
-              F: FnOnce(&mut CPU<M>, T)
-    {
-        let value = cpu.read(cpu.registers.db, address);
+    if sixteen_bits {
+        let value = fetch_word(cpu);
+        g(cpu, value);
+    } else {
+        let value = fetch(cpu);
         f(cpu, value);
     }
-    absolute_address(cpu,
-                     sixteen_bits,
-                     |cpu, address| address_reader(cpu, address, f),
-                     |cpu, address| address_reader(cpu, address, g));
 }
 
-fn absolute_address<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+fn implied<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
     where M: Mapper,
-          F: FnOnce(&mut CPU<M>, u16),
-          G: FnOnce(&mut CPU<M>, u16)
+          F: FnOnce(&mut CPU<M>),
+          G: FnOnce(&mut CPU<M>)
 {
-    let a = fetch(cpu);
-    let b = fetch(cpu);
-    let address = a as u16 | ((b as u16) << 8);
-
     if sixteen_bits {
-        g(cpu, address);
+        g(cpu);
     } else {
-        f(cpu, address);
+        f(cpu);
     }
 }
 
-fn immediate<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+// Read-modify-write addressing modes. Those pass a value to an opcode, and
+// store a value returned by it.
+
+/// Modifies a value in memory. Modifying takes an extra cycle between
+/// reading and writing. When modifying 16-bit values, the high byte is
+/// written first.
+fn modify<M, T, F>(cpu: &mut CPU<M>, address: Address, f: F)
     where M: Mapper,
-          F: FnOnce(&mut CPU<M>, u8),
-          G: FnOnce(&mut CPU<M>, u16)
+          T: BitWidth,
+          F: FnOnce(&mut CPU<M>, T) -> T
 {
-    let a = fetch(cpu);
+    let value = address.read(cpu);
+    idle(cpu);
+    let result = f(cpu, value).into();
+    if T::BITS == 16 {
+        address.offset(1).write(cpu, (result >> 8) as u8);
+    }
+    address.write(cpu, result as u8);
+}
+
+macro_rules! modify_mode {
+    ($name:ident, $address_mode:ident) => {
+        fn $name<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+            where M: Mapper,
+                  F: FnOnce(&mut CPU<M>, u8) -> u8,
+                  G: FnOnce(&mut CPU<M>, u16) -> u16
+        {
+            $address_mode(cpu,
+                          sixteen_bits,
+                          |cpu, address| modify(cpu, address, f),
+                          |cpu, address| modify(cpu, address, g));
+        }
+    }
+}
 
+modify_mode!(modify_absolute, absolute_address);
+modify_mode!(modify_absolute_x, absolute_x_address);
+modify_mode!(modify_direct, direct_address);
+modify_mode!(modify_direct_x, direct_x_address);
+
+fn accumulator<M, F, G>(cpu: &mut CPU<M>, sixteen_bits: bool, f: F, g: G)
+    where M: Mapper,
+          F: FnOnce(&mut CPU<M>, u8) -> u8,
+          G: FnOnce(&mut CPU<M>, u16) -> u16
+{
+    idle(cpu);
     if sixteen_bits {
-        let b = (fetch(cpu) as u16) << 8;
-        g(cpu, a as u16 | b);
+        let a = cpu.registers.a;
+        cpu.registers.a = g(cpu, a);
     } else {
-        f(cpu, a);
+        let a = cpu.registers.a as u8;
+        let result = f(cpu, a);
+        u8::set(&mut cpu.registers.a, result);
     }
 }
 
@@ -95,51 +554,1188 @@ fn pc_relative<M, F>(cpu: &mut CPU<M>, when: F)
 {
     let value = fetch(cpu);
     if when(cpu) {
-        cpu.registers.pc = cpu.registers.pc.wrapping_add(value as i8 as u16);
+        idle(cpu);
+        let pc = cpu.registers.pc;
+        let target = pc.wrapping_add(value as i8 as u16);
+        // Crossing a page takes an extra cycle in emulation mode.
+        if cpu.registers.emulation && pc & 0xFF00 != target & 0xFF00 {
+            idle(cpu);
+        }
+        cpu.registers.pc = target;
     }
 }
 
-fn a16<M, F, G, H>(cpu: &mut CPU<M>, f: F, g: G, h: H)
-    where M: Mapper,
-          F: FnOnce(&mut CPU<M>, bool, G, H)
-{
-    let sixteen_bits = cpu.registers.flags.contains(FLAG_A16);
-    f(cpu, sixteen_bits, g, h);
+fn pc_relative_long<M: Mapper>(cpu: &mut CPU<M>) {
+    let value = fetch_word(cpu);
+    idle(cpu);
+    cpu.registers.pc = cpu.registers.pc.wrapping_add(value);
+}
+
+fn flag_set<M: Mapper>(cpu: &CPU<M>, flag: Flags) -> bool {
+    cpu.registers.flags.contains(flag)
+}
+
+fn flag_clear<M: Mapper>(cpu: &CPU<M>, flag: Flags) -> bool {
+    !cpu.registers.flags.contains(flag)
 }
 
 // Assembly opcodes
 
+/// Adds a value with carry to the accumulator, handling decimal mode.
+///
+/// Subtraction is done by adding an inverted value, with decimal mode
+/// adjustment done in the other direction. Decimal mode is done one digit
+/// at a time, the same way the hardware does it, which matters for the
+/// overflow flag and invalid BCD values. Only the adjusted lower digits
+/// carry into the next one, anything above them comes through the carry.
+fn add_with_carry<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T, subtract: bool) {
+    let mask = (1i32 << T::BITS) - 1;
+    let sign = 1 << (T::BITS - 1);
+    let a = cpu.registers.a as i32 & mask;
+    let mut data = value.into() as i32;
+    if subtract {
+        data ^= mask;
+    }
+    let mut carry = cpu.registers.flags.contains(FLAG_CARRY) as i32;
+    let mut result;
+    let decimal = cpu.registers.flags.contains(FLAG_DECIMAL);
+    let last_digit = 4 * (T::BITS as i32 / 4 - 1);
+    if decimal {
+        result = 0;
+        for shift in (0..last_digit + 1).step_by(4) {
+            let digit = 0xF << shift;
+            let lower_digits = (1 << shift) - 1;
+            result = (a & digit) + (data & digit) + (carry << shift) + (result & lower_digits);
+            if shift == last_digit {
+                break;
+            }
+            if subtract && result < 0x10 << shift {
+                result -= 0x6 << shift;
+            } else if !subtract && result >= 0xA << shift {
+                result += 0x6 << shift;
+            }
+            carry = (result >= 0x10 << shift) as i32;
+        }
+    } else {
+        result = a + data + carry;
+    }
+    let overflow = !(a ^ data) & (a ^ result) & sign != 0;
+    if decimal {
+        if subtract && result <= mask {
+            result -= 0x6 << last_digit;
+        } else if !subtract && result >= 0xA << last_digit {
+            result += 0x6 << last_digit;
+        }
+    }
+    cpu.registers.flags.set(FLAG_CARRY, result > mask);
+    cpu.registers.flags.set(FLAG_OVERFLOW, overflow);
+    let result = T::get(result as u16);
+    T::set(&mut cpu.registers.a, result);
+    set_zn(cpu, result);
+}
+
+fn adc<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    add_with_carry(cpu, value, false);
+}
+
+fn sbc<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    add_with_carry(cpu, value, true);
+}
+
+fn and<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let result = T::get(cpu.registers.a & value.into());
+    T::set(&mut cpu.registers.a, result);
+    set_zn(cpu, result);
+}
+
+fn ora<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let result = T::get(cpu.registers.a | value.into());
+    T::set(&mut cpu.registers.a, result);
+    set_zn(cpu, result);
+}
+
+fn eor<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let result = T::get(cpu.registers.a ^ value.into());
+    T::set(&mut cpu.registers.a, result);
+    set_zn(cpu, result);
+}
+
+fn bit<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let value = value.into();
+    cpu.registers.flags.set(FLAG_NEGATIVE, value >> (T::BITS - 1) & 1 != 0);
+    cpu.registers.flags.set(FLAG_OVERFLOW, value >> (T::BITS - 2) & 1 != 0);
+    bit_immediate::<M, T>(cpu, T::get(value));
+}
+
+/// BIT with an immediate operand only changes the zero flag.
+fn bit_immediate<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let result = T::get(cpu.registers.a & value.into());
+    cpu.registers.flags.set(FLAG_ZERO, result.into() == 0);
+}
+
+fn compare<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, register: u16, value: T) {
+    let register = T::get(register).into();
+    let value = value.into();
+    cpu.registers.flags.set(FLAG_CARRY, register >= value);
+    set_zn(cpu, T::get(register.wrapping_sub(value)));
+}
+
+fn cmp<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let a = cpu.registers.a;
+    compare(cpu, a, value);
+}
+
+fn cpx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let x = cpu.registers.x;
+    compare(cpu, x, value);
+}
+
+fn cpy<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    let y = cpu.registers.y;
+    compare(cpu, y, value);
+}
+
+fn asl<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let value = value.into();
+    cpu.registers.flags.set(FLAG_CARRY, value >> (T::BITS - 1) & 1 != 0);
+    let result = T::get(value << 1);
+    set_zn(cpu, result);
+    result
+}
+
+fn lsr<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let value = value.into();
+    cpu.registers.flags.set(FLAG_CARRY, value & 1 != 0);
+    let result = T::get(value >> 1);
+    set_zn(cpu, result);
+    result
+}
+
+fn rol<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let value = value.into();
+    let carry = cpu.registers.flags.contains(FLAG_CARRY) as u16;
+    cpu.registers.flags.set(FLAG_CARRY, value >> (T::BITS - 1) & 1 != 0);
+    let result = T::get(value << 1 | carry);
+    set_zn(cpu, result);
+    result
+}
+
+fn ror<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let value = value.into();
+    let carry = cpu.registers.flags.contains(FLAG_CARRY) as u16;
+    cpu.registers.flags.set(FLAG_CARRY, value & 1 != 0);
+    let result = T::get(value >> 1 | carry << (T::BITS - 1));
+    set_zn(cpu, result);
+    result
+}
+
+fn inc<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let result = T::get(value.into().wrapping_add(1));
+    set_zn(cpu, result);
+    result
+}
+
+fn dec<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let result = T::get(value.into().wrapping_sub(1));
+    set_zn(cpu, result);
+    result
+}
+
+fn tsb<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let value = value.into();
+    let a = T::get(cpu.registers.a).into();
+    cpu.registers.flags.set(FLAG_ZERO, value & a == 0);
+    T::get(value | a)
+}
+
+fn trb<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) -> T {
+    let value = value.into();
+    let a = T::get(cpu.registers.a).into();
+    cpu.registers.flags.set(FLAG_ZERO, value & a == 0);
+    T::get(value & !a)
+}
+
 fn lda<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
     T::set(&mut cpu.registers.a, value);
+    set_zn(cpu, value);
+}
+
+fn ldx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn ldy<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, value: T) {
+    T::set(&mut cpu.registers.y, value);
+    set_zn(cpu, value);
+}
+
+fn sta<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, address: Address) {
+    let value = T::get(cpu.registers.a);
+    address.write(cpu, value);
+}
+
+fn stx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, address: Address) {
+    let value = T::get(cpu.registers.x);
+    address.write(cpu, value);
+}
+
+fn sty<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, address: Address) {
+    let value = T::get(cpu.registers.y);
+    address.write(cpu, value);
+}
+
+fn stz<M: Mapper, T: BitWidth + Default>(cpu: &mut CPU<M>, address: Address) {
+    address.write(cpu, T::default());
+}
+
+fn tax<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.a);
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn tay<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.a);
+    T::set(&mut cpu.registers.y, value);
+    set_zn(cpu, value);
+}
+
+fn txa<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.x);
+    T::set(&mut cpu.registers.a, value);
+    set_zn(cpu, value);
+}
+
+fn tya<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.y);
+    T::set(&mut cpu.registers.a, value);
+    set_zn(cpu, value);
+}
+
+fn tsx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.sp);
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn txy<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.x);
+    T::set(&mut cpu.registers.y, value);
+    set_zn(cpu, value);
+}
+
+fn tyx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.y);
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn inx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.x.wrapping_add(1));
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn iny<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.y.wrapping_add(1));
+    T::set(&mut cpu.registers.y, value);
+    set_zn(cpu, value);
+}
+
+fn dex<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.x.wrapping_sub(1));
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn dey<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.y.wrapping_sub(1));
+    T::set(&mut cpu.registers.y, value);
+    set_zn(cpu, value);
+}
+
+fn tcd<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    cpu.registers.dp = cpu.registers.a;
+    set_zn(cpu, cpu.registers.dp);
+}
+
+fn tdc<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    cpu.registers.a = cpu.registers.dp;
+    set_zn(cpu, cpu.registers.a);
+}
+
+fn tcs<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    cpu.registers.sp = if cpu.registers.emulation {
+        0x100 | (cpu.registers.a & 0xFF)
+    } else {
+        cpu.registers.a
+    };
+}
+
+fn txs<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    cpu.registers.sp = if cpu.registers.emulation {
+        0x100 | (cpu.registers.x & 0xFF)
+    } else {
+        cpu.registers.x
+    };
+}
+
+fn tsc<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    cpu.registers.a = cpu.registers.sp;
+    set_zn(cpu, cpu.registers.a);
+}
+
+// JSR and JSL push an address of their last byte, and RTS and RTL
+// increment a pulled address to get a next instruction.
+
+fn jsr<M: Mapper>(cpu: &mut CPU<M>) {
+    let address = fetch_word(cpu);
+    idle(cpu);
+    let pc = cpu.registers.pc.wrapping_sub(1);
+    push(cpu, pc);
+    cpu.registers.pc = address;
+}
+
+fn jsr_indirect<M: Mapper>(cpu: &mut CPU<M>) {
+    let pc = cpu.registers.pc.wrapping_add(1);
+    push_native(cpu, pc);
+    cpu.registers.pc = absolute_x_indirect(cpu);
+    end_native_stack(cpu);
+}
+
+fn jsl<M: Mapper>(cpu: &mut CPU<M>) {
+    let address = fetch_word(cpu);
+    let pb = cpu.registers.pb;
+    push_native(cpu, pb);
+    idle(cpu);
+    let bank = fetch(cpu);
+    let pc = cpu.registers.pc.wrapping_sub(1);
+    push_native(cpu, pc);
+    end_native_stack(cpu);
+    cpu.registers.pb = bank;
+    cpu.registers.pc = address;
+}
+
+fn rts<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let pc: u16 = pull(cpu);
+    idle(cpu);
+    cpu.registers.pc = pc.wrapping_add(1);
+}
+
+fn rtl<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let pc: u16 = pull_native(cpu);
+    cpu.registers.pb = pull_native(cpu);
+    end_native_stack(cpu);
+    cpu.registers.pc = pc.wrapping_add(1);
+}
+
+fn pha<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.a);
+    push(cpu, value);
+}
+
+fn phx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.x);
+    push(cpu, value);
+}
+
+fn phy<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let value = T::get(cpu.registers.y);
+    push(cpu, value);
+}
+
+fn pla<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let value = pull(cpu);
+    T::set(&mut cpu.registers.a, value);
+    set_zn(cpu, value);
+}
+
+fn plx<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let value = pull(cpu);
+    T::set(&mut cpu.registers.x, value);
+    set_zn(cpu, value);
+}
+
+fn ply<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let value = pull(cpu);
+    T::set(&mut cpu.registers.y, value);
+    set_zn(cpu, value);
 }
 
-fn stz<M: Mapper, T: BitWidth + Default>(cpu: &mut CPU<M>, address: u16) {
+fn php<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let status = cpu.registers.status();
+    push(cpu, status);
+}
+
+fn phb<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
     let db = cpu.registers.db;
-    cpu.write(db, address, T::default());
+    push(cpu, db);
 }
 
-pub fn run_instruction<M: Mapper>(cpu: &mut CPU<M>) {
-    match fetch(cpu) {
-        // BRA
-        0x80 => pc_relative(cpu, |_| true),
-
-        // LDA (Load Accumulator from Memory)
-        // immediate
-        0xA9 => a16(cpu, immediate, lda, lda),
-        // absolute
-        0xAD => a16(cpu, absolute, lda, lda),
-
-        // SEI (Set Interrupt Disable Flag)
-        // implied
-        0x78 => set_flag(cpu, FLAG_NO_IRQ),
-
-        // STZ (Store Zero to Memory)
-        // absolute
-        0x9C => a16(cpu, absolute_address, stz::<M, u8>, stz::<M, u16>),
-
-        code => {
-            println!("Tried to run unimplemented instruction {:x}.", code);
-            unimplemented!();
+fn phk<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let pb = cpu.registers.pb;
+    push(cpu, pb);
+}
+
+fn phd<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let dp = cpu.registers.dp;
+    push_native(cpu, dp);
+    end_native_stack(cpu);
+}
+
+fn plp<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let status = pull(cpu);
+    cpu.registers.set_status(status);
+}
+
+fn plb<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let db = pull_native(cpu);
+    end_native_stack(cpu);
+    cpu.registers.db = db;
+    set_zn(cpu, db);
+}
+
+fn pld<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let dp = pull_native(cpu);
+    end_native_stack(cpu);
+    cpu.registers.dp = dp;
+    set_zn(cpu, dp);
+}
+
+fn pea<M: Mapper>(cpu: &mut CPU<M>) {
+    let value = fetch_word(cpu);
+    push_native(cpu, value);
+    end_native_stack(cpu);
+}
+
+fn pei<M: Mapper>(cpu: &mut CPU<M>) {
+    let offset = fetch_direct_offset(cpu);
+    let value: u16 = Address::bank_zero(cpu.registers.dp.wrapping_add(offset)).read(cpu);
+    push_native(cpu, value);
+    end_native_stack(cpu);
+}
+
+fn per<M: Mapper>(cpu: &mut CPU<M>) {
+    let offset = fetch_word(cpu);
+    idle(cpu);
+    let value = cpu.registers.pc.wrapping_add(offset);
+    push_native(cpu, value);
+    end_native_stack(cpu);
+}
+
+/// Copies a single byte of a block move. The instruction is repeated until
+/// the accumulator wraps around, so interrupts can happen during a copy.
+fn block_move<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>, step: u16) {
+    let destination = fetch(cpu);
+    let source = fetch(cpu);
+    cpu.registers.db = destination;
+    let value: u8 = cpu.read(source, cpu.registers.x);
+    cpu.write(destination, cpu.registers.y, value);
+    idle(cpu);
+    let x = T::get(cpu.registers.x.wrapping_add(step));
+    T::set(&mut cpu.registers.x, x);
+    let y = T::get(cpu.registers.y.wrapping_add(step));
+    T::set(&mut cpu.registers.y, y);
+    idle(cpu);
+    cpu.registers.a = cpu.registers.a.wrapping_sub(1);
+    if cpu.registers.a != 0xFFFF {
+        cpu.registers.pc = cpu.registers.pc.wrapping_sub(3);
+    }
+}
+
+fn mvn<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    block_move::<M, T>(cpu, 1);
+}
+
+fn mvp<M: Mapper, T: BitWidth>(cpu: &mut CPU<M>) {
+    block_move::<M, T>(cpu, 0xFFFF);
+}
+
+fn rep<M: Mapper>(cpu: &mut CPU<M>) {
+    let mask = fetch(cpu);
+    idle(cpu);
+    let status = cpu.registers.status() & !mask;
+    cpu.registers.set_status(status);
+}
+
+fn sep<M: Mapper>(cpu: &mut CPU<M>) {
+    let mask = fetch(cpu);
+    idle(cpu);
+    let status = cpu.registers.status() | mask;
+    cpu.registers.set_status(status);
+}
+
+fn xce<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    let carry = cpu.registers.flags.contains(FLAG_CARRY);
+    let emulation = cpu.registers.emulation;
+    cpu.registers.flags.set(FLAG_CARRY, emulation);
+    cpu.registers.set_emulation(carry);
+}
+
+fn xba<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    cpu.registers.a = cpu.registers.a.rotate_left(8);
+    // Flags are set based on the new low byte, even in 16-bit mode.
+    set_zn(cpu, cpu.registers.a as u8);
+}
+
+// Interrupts
+
+/// Pushes a return frame and jumps to an interrupt handler.
+///
+/// BRK and COP are two bytes long, the second byte is a signature ignored
+/// by the processor. Reset doesn't go through here, see `CPU::reset`.
+pub fn interrupt<M: Mapper>(cpu: &mut CPU<M>, interrupt: Interrupt) {
+    let software = interrupt == Interrupt::Brk || interrupt == Interrupt::Cop;
+    if software {
+        fetch(cpu);
+    } else {
+        idle(cpu);
+        idle(cpu);
+    }
+    let emulation = cpu.registers.emulation;
+    if !emulation {
+        let pb = cpu.registers.pb;
+        push(cpu, pb);
+    }
+    let pc = cpu.registers.pc;
+    push(cpu, pc);
+    let mut status = cpu.registers.status();
+    // In emulation mode bit 4 is the break flag, telling BRK apart from
+    // IRQ, as both share the same vector.
+    if emulation && interrupt != Interrupt::Brk {
+        status &= !0x10;
+    }
+    push(cpu, status);
+    cpu.registers.flags.insert(FLAG_NO_IRQ);
+    cpu.registers.flags.remove(FLAG_DECIMAL);
+    cpu.registers.pb = 0;
+    cpu.registers.pc = cpu.read(0, interrupt.vector(emulation));
+}
+
+fn rti<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    let status = pull(cpu);
+    cpu.registers.set_status(status);
+    cpu.registers.pc = pull(cpu);
+    if !cpu.registers.emulation {
+        cpu.registers.pb = pull(cpu);
+    }
+}
+
+fn wai<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    cpu.state = State::Waiting;
+}
+
+fn stp<M: Mapper>(cpu: &mut CPU<M>) {
+    idle(cpu);
+    idle(cpu);
+    cpu.state = State::Stopped;
+}
+
+// Opcode table
+//
+// Every opcode is described once in `instructions!`, which generates an
+// opcode table for each combination of register widths. Handlers know the
+// register widths at compile time, so they don't need to check them.
+//
+// An entry lists a mnemonic, an addressing mode, which register decides the
+// operand size (M for the accumulator, X for index registers), and base
+// cycles followed by penalties: `m` for each extra cycle when the
+// accumulator is 16-bit, `x` when index registers are 16-bit, and `n` in
+// native mode.
+
+/// Register widths an opcode table is specialized for.
+trait Widths {
+    const A16: bool;
+    const XY16: bool;
+    const EMULATION: bool;
+}
+
+macro_rules! widths {
+    ($name:ident, $a16:expr, $xy16:expr, $emulation:expr) => {
+        enum $name {}
+
+        impl Widths for $name {
+            const A16: bool = $a16;
+            const XY16: bool = $xy16;
+            const EMULATION: bool = $emulation;
+        }
+    }
+}
+
+widths!(Emulation, false, false, true);
+widths!(A8XY8, false, false, false);
+widths!(A8XY16, false, true, false);
+widths!(A16XY8, true, false, false);
+widths!(A16XY16, true, true, false);
+
+macro_rules! width {
+    (-) => (Width::None);
+    (M) => (Width::Accumulator);
+    (X) => (Width::Index);
+}
+
+macro_rules! sixteen_bits {
+    ($widths:ident, -) => (false);
+    ($widths:ident, M) => ($widths::A16);
+    ($widths:ident, X) => ($widths::XY16);
+}
+
+macro_rules! penalty {
+    ($widths:ident, m) => ($widths::A16 as u8);
+    ($widths:ident, x) => ($widths::XY16 as u8);
+    ($widths:ident, n) => (!$widths::EMULATION as u8);
+}
+
+macro_rules! instructions {
+    ($($opcode:literal => $mnemonic:ident $mode:ident $width:tt $cycles:literal
+       [$($penalty:ident)*] |$cpu:ident| $handler:expr,)*) => {
+        const fn instruction<M: Mapper, W: Widths>(opcode: u8) -> Instruction<M> {
+            match opcode {
+                $(
+                    $opcode => {
+                        fn handler<M: Mapper, W: Widths>($cpu: &mut CPU<M>) {
+                            $handler;
+                        }
+                        Instruction {
+                            mnemonic: stringify!($mnemonic),
+                            mode: AddressingMode::$mode,
+                            width: width!($width),
+                            sixteen_bits: sixteen_bits!(W, $width),
+                            cycles: $cycles $(+ penalty!(W, $penalty))*,
+                            handler: handler::<M, W>,
+                        }
+                    }
+                )*
+            }
         }
     }
 }
+
+instructions! {
+    // ADC (Add With Carry)
+    0x69 => ADC Immediate M 2 [m] |cpu| immediate(cpu, W::A16, adc, adc),
+    0x6D => ADC Absolute M 4 [m] |cpu| absolute(cpu, W::A16, adc, adc),
+    0x7D => ADC AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, adc, adc),
+    0x79 => ADC AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, adc, adc),
+    0x6F => ADC AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, adc, adc),
+    0x7F => ADC AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, adc, adc),
+    0x65 => ADC Direct M 3 [m] |cpu| direct(cpu, W::A16, adc, adc),
+    0x75 => ADC DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, adc, adc),
+    0x72 => ADC DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, adc, adc),
+    0x67 => ADC DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, adc, adc),
+    0x61 => ADC DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, adc, adc),
+    0x71 => ADC DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, adc, adc),
+    0x77 => ADC DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, adc, adc),
+    0x63 => ADC StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, adc, adc),
+    0x73 => ADC StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, adc, adc),
+
+    // AND (AND Accumulator with Memory)
+    0x29 => AND Immediate M 2 [m] |cpu| immediate(cpu, W::A16, and, and),
+    0x2D => AND Absolute M 4 [m] |cpu| absolute(cpu, W::A16, and, and),
+    0x3D => AND AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, and, and),
+    0x39 => AND AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, and, and),
+    0x2F => AND AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, and, and),
+    0x3F => AND AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, and, and),
+    0x25 => AND Direct M 3 [m] |cpu| direct(cpu, W::A16, and, and),
+    0x35 => AND DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, and, and),
+    0x32 => AND DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, and, and),
+    0x27 => AND DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, and, and),
+    0x21 => AND DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, and, and),
+    0x31 => AND DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, and, and),
+    0x37 => AND DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, and, and),
+    0x23 => AND StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, and, and),
+    0x33 => AND StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, and, and),
+
+    // ASL (Shift Memory or Accumulator Left)
+    0x0A => ASL Accumulator M 2 [] |cpu| accumulator(cpu, W::A16, asl, asl),
+    0x0E => ASL Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, asl, asl),
+    0x1E => ASL AbsoluteX M 7 [m m] |cpu| modify_absolute_x(cpu, W::A16, asl, asl),
+    0x06 => ASL Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, asl, asl),
+    0x16 => ASL DirectX M 6 [m m] |cpu| modify_direct_x(cpu, W::A16, asl, asl),
+
+    // BCC (Branch if Carry Clear)
+    0x90 => BCC Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_clear(cpu, FLAG_CARRY)),
+    // BCS (Branch if Carry Set)
+    0xB0 => BCS Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_set(cpu, FLAG_CARRY)),
+    // BEQ (Branch if Equal)
+    0xF0 => BEQ Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_set(cpu, FLAG_ZERO)),
+    // BMI (Branch if Minus)
+    0x30 => BMI Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_set(cpu, FLAG_NEGATIVE)),
+    // BNE (Branch if Not Equal)
+    0xD0 => BNE Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_clear(cpu, FLAG_ZERO)),
+    // BPL (Branch if Plus)
+    0x10 => BPL Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_clear(cpu, FLAG_NEGATIVE)),
+    // BRA (Branch Always)
+    0x80 => BRA Relative - 3 [] |cpu| pc_relative(cpu, |_| true),
+    // BRL (Branch Always Long)
+    0x82 => BRL RelativeLong - 4 [] |cpu| pc_relative_long(cpu),
+    // BVC (Branch if Overflow Clear)
+    0x50 => BVC Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_clear(cpu, FLAG_OVERFLOW)),
+    // BVS (Branch if Overflow Set)
+    0x70 => BVS Relative - 2 [] |cpu| pc_relative(cpu, |cpu| flag_set(cpu, FLAG_OVERFLOW)),
+
+    // BIT (Test Memory Bits against Accumulator)
+    0x89 => BIT Immediate M 2 [m] |cpu| immediate(cpu, W::A16, bit_immediate, bit_immediate),
+    0x2C => BIT Absolute M 4 [m] |cpu| absolute(cpu, W::A16, bit, bit),
+    0x3C => BIT AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, bit, bit),
+    0x24 => BIT Direct M 3 [m] |cpu| direct(cpu, W::A16, bit, bit),
+    0x34 => BIT DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, bit, bit),
+
+    // BRK (Software Break)
+    0x00 => BRK Immediate - 7 [n] |cpu| interrupt(cpu, Interrupt::Brk),
+
+    // CLC (Clear Carry Flag)
+    0x18 => CLC Implied - 2 [] |cpu| clear_flag(cpu, FLAG_CARRY),
+    // CLD (Clear Decimal Mode Flag)
+    0xD8 => CLD Implied - 2 [] |cpu| clear_flag(cpu, FLAG_DECIMAL),
+    // CLI (Clear Interrupt Disable Flag)
+    0x58 => CLI Implied - 2 [] |cpu| clear_flag(cpu, FLAG_NO_IRQ),
+    // CLV (Clear Overflow Flag)
+    0xB8 => CLV Implied - 2 [] |cpu| clear_flag(cpu, FLAG_OVERFLOW),
+
+    // CMP (Compare Accumulator with Memory)
+    0xC9 => CMP Immediate M 2 [m] |cpu| immediate(cpu, W::A16, cmp, cmp),
+    0xCD => CMP Absolute M 4 [m] |cpu| absolute(cpu, W::A16, cmp, cmp),
+    0xDD => CMP AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, cmp, cmp),
+    0xD9 => CMP AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, cmp, cmp),
+    0xCF => CMP AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, cmp, cmp),
+    0xDF => CMP AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, cmp, cmp),
+    0xC5 => CMP Direct M 3 [m] |cpu| direct(cpu, W::A16, cmp, cmp),
+    0xD5 => CMP DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, cmp, cmp),
+    0xD2 => CMP DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, cmp, cmp),
+    0xC7 => CMP DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, cmp, cmp),
+    0xC1 => CMP DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, cmp, cmp),
+    0xD1 => CMP DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, cmp, cmp),
+    0xD7 => CMP DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, cmp, cmp),
+    0xC3 => CMP StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, cmp, cmp),
+    0xD3 => CMP StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, cmp, cmp),
+
+    // COP (Co-Processor Enable)
+    0x02 => COP Immediate - 7 [n] |cpu| interrupt(cpu, Interrupt::Cop),
+
+    // CPX (Compare Index Register X with Memory)
+    0xE0 => CPX Immediate X 2 [x] |cpu| immediate(cpu, W::XY16, cpx, cpx),
+    0xEC => CPX Absolute X 4 [x] |cpu| absolute(cpu, W::XY16, cpx, cpx),
+    0xE4 => CPX Direct X 3 [x] |cpu| direct(cpu, W::XY16, cpx, cpx),
+
+    // CPY (Compare Index Register Y with Memory)
+    0xC0 => CPY Immediate X 2 [x] |cpu| immediate(cpu, W::XY16, cpy, cpy),
+    0xCC => CPY Absolute X 4 [x] |cpu| absolute(cpu, W::XY16, cpy, cpy),
+    0xC4 => CPY Direct X 3 [x] |cpu| direct(cpu, W::XY16, cpy, cpy),
+
+    // DEC (Decrement)
+    0x3A => DEC Accumulator M 2 [] |cpu| accumulator(cpu, W::A16, dec, dec),
+    0xCE => DEC Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, dec, dec),
+    0xDE => DEC AbsoluteX M 7 [m m] |cpu| modify_absolute_x(cpu, W::A16, dec, dec),
+    0xC6 => DEC Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, dec, dec),
+    0xD6 => DEC DirectX M 6 [m m] |cpu| modify_direct_x(cpu, W::A16, dec, dec),
+
+    // DEX (Decrement Index Register X)
+    0xCA => DEX Implied X 2 [] |cpu| implied(cpu, W::XY16, dex::<M, u8>, dex::<M, u16>),
+    // DEY (Decrement Index Register Y)
+    0x88 => DEY Implied X 2 [] |cpu| implied(cpu, W::XY16, dey::<M, u8>, dey::<M, u16>),
+
+    // EOR (Exclusive-OR Accumulator with Memory)
+    0x49 => EOR Immediate M 2 [m] |cpu| immediate(cpu, W::A16, eor, eor),
+    0x4D => EOR Absolute M 4 [m] |cpu| absolute(cpu, W::A16, eor, eor),
+    0x5D => EOR AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, eor, eor),
+    0x59 => EOR AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, eor, eor),
+    0x4F => EOR AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, eor, eor),
+    0x5F => EOR AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, eor, eor),
+    0x45 => EOR Direct M 3 [m] |cpu| direct(cpu, W::A16, eor, eor),
+    0x55 => EOR DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, eor, eor),
+    0x52 => EOR DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, eor, eor),
+    0x47 => EOR DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, eor, eor),
+    0x41 => EOR DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, eor, eor),
+    0x51 => EOR DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, eor, eor),
+    0x57 => EOR DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, eor, eor),
+    0x43 => EOR StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, eor, eor),
+    0x53 => EOR StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, eor, eor),
+
+    // INC (Increment)
+    0x1A => INC Accumulator M 2 [] |cpu| accumulator(cpu, W::A16, inc, inc),
+    0xEE => INC Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, inc, inc),
+    0xFE => INC AbsoluteX M 7 [m m] |cpu| modify_absolute_x(cpu, W::A16, inc, inc),
+    0xE6 => INC Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, inc, inc),
+    0xF6 => INC DirectX M 6 [m m] |cpu| modify_direct_x(cpu, W::A16, inc, inc),
+
+    // INX (Increment Index Register X)
+    0xE8 => INX Implied X 2 [] |cpu| implied(cpu, W::XY16, inx::<M, u8>, inx::<M, u16>),
+    // INY (Increment Index Register Y)
+    0xC8 => INY Implied X 2 [] |cpu| implied(cpu, W::XY16, iny::<M, u8>, iny::<M, u16>),
+
+    // JML (Jump Long)
+    0x5C => JML AbsoluteLong - 4 [] |cpu| {
+        let address = fetch_word(cpu);
+        cpu.registers.pb = fetch(cpu);
+        cpu.registers.pc = address;
+    },
+    0xDC => JML AbsoluteIndirectLong - 6 [] |cpu| {
+        let address = absolute_indirect_long(cpu);
+        cpu.registers.pb = address.bank;
+        cpu.registers.pc = address.address;
+    },
+
+    // JMP (Jump)
+    0x4C => JMP Absolute - 3 [] |cpu| cpu.registers.pc = fetch_word(cpu),
+    0x6C => JMP AbsoluteIndirect - 5 [] |cpu| cpu.registers.pc = absolute_indirect(cpu),
+    0x7C => JMP AbsoluteXIndirect - 6 [] |cpu| cpu.registers.pc = absolute_x_indirect(cpu),
+
+    // JSL (Jump to Subroutine Long)
+    0x22 => JSL AbsoluteLong - 8 [] |cpu| jsl(cpu),
+
+    // JSR (Jump to Subroutine)
+    0x20 => JSR Absolute - 6 [] |cpu| jsr(cpu),
+    0xFC => JSR AbsoluteXIndirect - 8 [] |cpu| jsr_indirect(cpu),
+
+    // LDA (Load Accumulator from Memory)
+    0xA9 => LDA Immediate M 2 [m] |cpu| immediate(cpu, W::A16, lda, lda),
+    0xAD => LDA Absolute M 4 [m] |cpu| absolute(cpu, W::A16, lda, lda),
+    0xBD => LDA AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, lda, lda),
+    0xB9 => LDA AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, lda, lda),
+    0xAF => LDA AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, lda, lda),
+    0xBF => LDA AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, lda, lda),
+    0xA5 => LDA Direct M 3 [m] |cpu| direct(cpu, W::A16, lda, lda),
+    0xB5 => LDA DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, lda, lda),
+    0xB2 => LDA DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, lda, lda),
+    0xA7 => LDA DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, lda, lda),
+    0xA1 => LDA DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, lda, lda),
+    0xB1 => LDA DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, lda, lda),
+    0xB7 => LDA DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, lda, lda),
+    0xA3 => LDA StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, lda, lda),
+    0xB3 => LDA StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, lda, lda),
+
+    // LDX (Load Index Register X from Memory)
+    0xA2 => LDX Immediate X 2 [x] |cpu| immediate(cpu, W::XY16, ldx, ldx),
+    0xAE => LDX Absolute X 4 [x] |cpu| absolute(cpu, W::XY16, ldx, ldx),
+    0xBE => LDX AbsoluteY X 4 [x x] |cpu| absolute_y(cpu, W::XY16, ldx, ldx),
+    0xA6 => LDX Direct X 3 [x] |cpu| direct(cpu, W::XY16, ldx, ldx),
+    0xB6 => LDX DirectY X 4 [x] |cpu| direct_y(cpu, W::XY16, ldx, ldx),
+
+    // LDY (Load Index Register Y from Memory)
+    0xA0 => LDY Immediate X 2 [x] |cpu| immediate(cpu, W::XY16, ldy, ldy),
+    0xAC => LDY Absolute X 4 [x] |cpu| absolute(cpu, W::XY16, ldy, ldy),
+    0xBC => LDY AbsoluteX X 4 [x x] |cpu| absolute_x(cpu, W::XY16, ldy, ldy),
+    0xA4 => LDY Direct X 3 [x] |cpu| direct(cpu, W::XY16, ldy, ldy),
+    0xB4 => LDY DirectX X 4 [x] |cpu| direct_x(cpu, W::XY16, ldy, ldy),
+
+    // LSR (Logical Shift Memory or Accumulator Right)
+    0x4A => LSR Accumulator M 2 [] |cpu| accumulator(cpu, W::A16, lsr, lsr),
+    0x4E => LSR Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, lsr, lsr),
+    0x5E => LSR AbsoluteX M 7 [m m] |cpu| modify_absolute_x(cpu, W::A16, lsr, lsr),
+    0x46 => LSR Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, lsr, lsr),
+    0x56 => LSR DirectX M 6 [m m] |cpu| modify_direct_x(cpu, W::A16, lsr, lsr),
+
+    // MVN (Block Move Next)
+    0x54 => MVN BlockMove X 7 [] |cpu| implied(cpu, W::XY16, mvn::<M, u8>, mvn::<M, u16>),
+    // MVP (Block Move Previous)
+    0x44 => MVP BlockMove X 7 [] |cpu| implied(cpu, W::XY16, mvp::<M, u8>, mvp::<M, u16>),
+
+    // NOP (No Operation)
+    0xEA => NOP Implied - 2 [] |cpu| idle(cpu),
+
+    // ORA (OR Accumulator with Memory)
+    0x09 => ORA Immediate M 2 [m] |cpu| immediate(cpu, W::A16, ora, ora),
+    0x0D => ORA Absolute M 4 [m] |cpu| absolute(cpu, W::A16, ora, ora),
+    0x1D => ORA AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, ora, ora),
+    0x19 => ORA AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, ora, ora),
+    0x0F => ORA AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, ora, ora),
+    0x1F => ORA AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, ora, ora),
+    0x05 => ORA Direct M 3 [m] |cpu| direct(cpu, W::A16, ora, ora),
+    0x15 => ORA DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, ora, ora),
+    0x12 => ORA DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, ora, ora),
+    0x07 => ORA DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, ora, ora),
+    0x01 => ORA DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, ora, ora),
+    0x11 => ORA DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, ora, ora),
+    0x17 => ORA DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, ora, ora),
+    0x03 => ORA StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, ora, ora),
+    0x13 => ORA StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, ora, ora),
+
+    // PEA (Push Effective Absolute Address)
+    0xF4 => PEA Absolute - 5 [] |cpu| pea(cpu),
+    // PEI (Push Effective Indirect Address)
+    0xD4 => PEI DirectIndirect - 6 [] |cpu| pei(cpu),
+    // PER (Push Effective PC Relative Indirect Address)
+    0x62 => PER RelativeLong - 6 [] |cpu| per(cpu),
+
+    // PHA (Push Accumulator)
+    0x48 => PHA Implied M 3 [m] |cpu| implied(cpu, W::A16, pha::<M, u8>, pha::<M, u16>),
+    // PHB (Push Data Bank Register)
+    0x8B => PHB Implied - 3 [] |cpu| phb(cpu),
+    // PHD (Push Direct Page Register)
+    0x0B => PHD Implied - 4 [] |cpu| phd(cpu),
+    // PHK (Push Program Bank Register)
+    0x4B => PHK Implied - 3 [] |cpu| phk(cpu),
+    // PHP (Push Processor Status Register)
+    0x08 => PHP Implied - 3 [] |cpu| php(cpu),
+    // PHX (Push Index Register X)
+    0xDA => PHX Implied X 3 [x] |cpu| implied(cpu, W::XY16, phx::<M, u8>, phx::<M, u16>),
+    // PHY (Push Index Register Y)
+    0x5A => PHY Implied X 3 [x] |cpu| implied(cpu, W::XY16, phy::<M, u8>, phy::<M, u16>),
+
+    // PLA (Pull Accumulator)
+    0x68 => PLA Implied M 4 [m] |cpu| implied(cpu, W::A16, pla::<M, u8>, pla::<M, u16>),
+    // PLB (Pull Data Bank Register)
+    0xAB => PLB Implied - 4 [] |cpu| plb(cpu),
+    // PLD (Pull Direct Page Register)
+    0x2B => PLD Implied - 5 [] |cpu| pld(cpu),
+    // PLP (Pull Processor Status Register)
+    0x28 => PLP Implied - 4 [] |cpu| plp(cpu),
+    // PLX (Pull Index Register X)
+    0xFA => PLX Implied X 4 [x] |cpu| implied(cpu, W::XY16, plx::<M, u8>, plx::<M, u16>),
+    // PLY (Pull Index Register Y)
+    0x7A => PLY Implied X 4 [x] |cpu| implied(cpu, W::XY16, ply::<M, u8>, ply::<M, u16>),
+
+    // REP (Reset Processor Status Bits)
+    0xC2 => REP Immediate - 3 [] |cpu| rep(cpu),
+
+    // ROL (Rotate Memory or Accumulator Left)
+    0x2A => ROL Accumulator M 2 [] |cpu| accumulator(cpu, W::A16, rol, rol),
+    0x2E => ROL Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, rol, rol),
+    0x3E => ROL AbsoluteX M 7 [m m] |cpu| modify_absolute_x(cpu, W::A16, rol, rol),
+    0x26 => ROL Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, rol, rol),
+    0x36 => ROL DirectX M 6 [m m] |cpu| modify_direct_x(cpu, W::A16, rol, rol),
+
+    // ROR (Rotate Memory or Accumulator Right)
+    0x6A => ROR Accumulator M 2 [] |cpu| accumulator(cpu, W::A16, ror, ror),
+    0x6E => ROR Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, ror, ror),
+    0x7E => ROR AbsoluteX M 7 [m m] |cpu| modify_absolute_x(cpu, W::A16, ror, ror),
+    0x66 => ROR Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, ror, ror),
+    0x76 => ROR DirectX M 6 [m m] |cpu| modify_direct_x(cpu, W::A16, ror, ror),
+
+    // RTI (Return from Interrupt)
+    0x40 => RTI Implied - 6 [n] |cpu| rti(cpu),
+
+    // RTL (Return from Subroutine Long)
+    0x6B => RTL Implied - 6 [] |cpu| rtl(cpu),
+
+    // RTS (Return from Subroutine)
+    0x60 => RTS Implied - 6 [] |cpu| rts(cpu),
+
+    // SBC (Subtract with Borrow from Accumulator)
+    0xE9 => SBC Immediate M 2 [m] |cpu| immediate(cpu, W::A16, sbc, sbc),
+    0xED => SBC Absolute M 4 [m] |cpu| absolute(cpu, W::A16, sbc, sbc),
+    0xFD => SBC AbsoluteX M 4 [m x] |cpu| absolute_x(cpu, W::A16, sbc, sbc),
+    0xF9 => SBC AbsoluteY M 4 [m x] |cpu| absolute_y(cpu, W::A16, sbc, sbc),
+    0xEF => SBC AbsoluteLong M 5 [m] |cpu| absolute_long(cpu, W::A16, sbc, sbc),
+    0xFF => SBC AbsoluteLongX M 5 [m] |cpu| absolute_long_x(cpu, W::A16, sbc, sbc),
+    0xE5 => SBC Direct M 3 [m] |cpu| direct(cpu, W::A16, sbc, sbc),
+    0xF5 => SBC DirectX M 4 [m] |cpu| direct_x(cpu, W::A16, sbc, sbc),
+    0xF2 => SBC DirectIndirect M 5 [m] |cpu| direct_indirect(cpu, W::A16, sbc, sbc),
+    0xE7 => SBC DirectIndirectLong M 6 [m] |cpu| direct_indirect_long(cpu, W::A16, sbc, sbc),
+    0xE1 => SBC DirectXIndirect M 6 [m] |cpu| direct_x_indirect(cpu, W::A16, sbc, sbc),
+    0xF1 => SBC DirectIndirectY M 5 [m x] |cpu| direct_indirect_y(cpu, W::A16, sbc, sbc),
+    0xF7 => SBC DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y(cpu, W::A16, sbc, sbc),
+    0xE3 => SBC StackRelative M 4 [m] |cpu| stack_relative(cpu, W::A16, sbc, sbc),
+    0xF3 => SBC StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y(cpu, W::A16, sbc, sbc),
+
+    // SEC (Set Carry Flag)
+    0x38 => SEC Implied - 2 [] |cpu| set_flag(cpu, FLAG_CARRY),
+    // SED (Set Decimal Flag)
+    0xF8 => SED Implied - 2 [] |cpu| set_flag(cpu, FLAG_DECIMAL),
+    // SEI (Set Interrupt Disable Flag)
+    0x78 => SEI Implied - 2 [] |cpu| set_flag(cpu, FLAG_NO_IRQ),
+
+    // SEP (Set Processor Status Bits)
+    0xE2 => SEP Immediate - 3 [] |cpu| sep(cpu),
+
+    // STA (Store Accumulator to Memory)
+    0x8D => STA Absolute M 4 [m]
+        |cpu| absolute_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x9D => STA AbsoluteX M 5 [m]
+        |cpu| absolute_x_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x99 => STA AbsoluteY M 5 [m]
+        |cpu| absolute_y_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x8F => STA AbsoluteLong M 5 [m]
+        |cpu| absolute_long_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x9F => STA AbsoluteLongX M 5 [m]
+        |cpu| absolute_long_x_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x85 => STA Direct M 3 [m] |cpu| direct_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x95 => STA DirectX M 4 [m]
+        |cpu| direct_x_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x92 => STA DirectIndirect M 5 [m]
+        |cpu| direct_indirect_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x87 => STA DirectIndirectLong M 6 [m]
+        |cpu| direct_indirect_long_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x81 => STA DirectXIndirect M 6 [m]
+        |cpu| direct_x_indirect_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x91 => STA DirectIndirectY M 6 [m]
+        |cpu| direct_indirect_y_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x97 => STA DirectIndirectLongY M 6 [m]
+        |cpu| direct_indirect_long_y_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x83 => STA StackRelative M 4 [m]
+        |cpu| stack_relative_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+    0x93 => STA StackRelativeIndirectY M 7 [m]
+        |cpu| stack_relative_indirect_y_address(cpu, W::A16, sta::<M, u8>, sta::<M, u16>),
+
+    // STP (Stop the Processor)
+    0xDB => STP Implied - 3 [] |cpu| stp(cpu),
+
+    // STX (Store Index Register X to Memory)
+    0x8E => STX Absolute X 4 [x]
+        |cpu| absolute_address(cpu, W::XY16, stx::<M, u8>, stx::<M, u16>),
+    0x86 => STX Direct X 3 [x] |cpu| direct_address(cpu, W::XY16, stx::<M, u8>, stx::<M, u16>),
+    0x96 => STX DirectY X 4 [x]
+        |cpu| direct_y_address(cpu, W::XY16, stx::<M, u8>, stx::<M, u16>),
+
+    // STY (Store Index Register Y to Memory)
+    0x8C => STY Absolute X 4 [x]
+        |cpu| absolute_address(cpu, W::XY16, sty::<M, u8>, sty::<M, u16>),
+    0x84 => STY Direct X 3 [x] |cpu| direct_address(cpu, W::XY16, sty::<M, u8>, sty::<M, u16>),
+    0x94 => STY DirectX X 4 [x]
+        |cpu| direct_x_address(cpu, W::XY16, sty::<M, u8>, sty::<M, u16>),
+
+    // STZ (Store Zero to Memory)
+    0x9C => STZ Absolute M 4 [m]
+        |cpu| absolute_address(cpu, W::A16, stz::<M, u8>, stz::<M, u16>),
+    0x9E => STZ AbsoluteX M 5 [m]
+        |cpu| absolute_x_address(cpu, W::A16, stz::<M, u8>, stz::<M, u16>),
+    0x64 => STZ Direct M 3 [m] |cpu| direct_address(cpu, W::A16, stz::<M, u8>, stz::<M, u16>),
+    0x74 => STZ DirectX M 4 [m]
+        |cpu| direct_x_address(cpu, W::A16, stz::<M, u8>, stz::<M, u16>),
+
+    // TAX (Transfer Accumulator to Index Register X)
+    0xAA => TAX Implied X 2 [] |cpu| implied(cpu, W::XY16, tax::<M, u8>, tax::<M, u16>),
+    // TAY (Transfer Accumulator to Index Register Y)
+    0xA8 => TAY Implied X 2 [] |cpu| implied(cpu, W::XY16, tay::<M, u8>, tay::<M, u16>),
+    // TXA (Transfer Index Register X to Accumulator)
+    0x8A => TXA Implied M 2 [] |cpu| implied(cpu, W::A16, txa::<M, u8>, txa::<M, u16>),
+    // TYA (Transfer Index Register Y to Accumulator)
+    0x98 => TYA Implied M 2 [] |cpu| implied(cpu, W::A16, tya::<M, u8>, tya::<M, u16>),
+    // TSX (Transfer Stack Pointer to Index Register X)
+    0xBA => TSX Implied X 2 [] |cpu| implied(cpu, W::XY16, tsx::<M, u8>, tsx::<M, u16>),
+    // TXS (Transfer Index Register X to Stack Pointer)
+    0x9A => TXS Implied - 2 [] |cpu| txs(cpu),
+    // TXY (Transfer Index Register X to Index Register Y)
+    0x9B => TXY Implied X 2 [] |cpu| implied(cpu, W::XY16, txy::<M, u8>, txy::<M, u16>),
+    // TYX (Transfer Index Register Y to Index Register X)
+    0xBB => TYX Implied X 2 [] |cpu| implied(cpu, W::XY16, tyx::<M, u8>, tyx::<M, u16>),
+    // TCD (Transfer 16-bit Accumulator to Direct Page Register)
+    0x5B => TCD Implied - 2 [] |cpu| tcd(cpu),
+    // TDC (Transfer Direct Page Register to 16-bit Accumulator)
+    0x7B => TDC Implied - 2 [] |cpu| tdc(cpu),
+    // TCS (Transfer 16-bit Accumulator to Stack Pointer)
+    0x1B => TCS Implied - 2 [] |cpu| tcs(cpu),
+    // TSC (Transfer Stack Pointer to 16-bit Accumulator)
+    0x3B => TSC Implied - 2 [] |cpu| tsc(cpu),
+    // XBA (Exchange B and A Accumulators)
+    0xEB => XBA Implied - 3 [] |cpu| xba(cpu),
+
+    // TRB (Test and Reset Memory Bits Against Accumulator)
+    0x1C => TRB Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, trb, trb),
+    0x14 => TRB Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, trb, trb),
+
+    // TSB (Test and Set Memory Bits Against Accumulator)
+    0x0C => TSB Absolute M 6 [m m] |cpu| modify_absolute(cpu, W::A16, tsb, tsb),
+    0x04 => TSB Direct M 5 [m m] |cpu| modify_direct(cpu, W::A16, tsb, tsb),
+
+    // WAI (Wait for Interrupt)
+    0xCB => WAI Implied - 3 [] |cpu| wai(cpu),
+
+    // WDM (Reserved for Future Expansion)
+    0x42 => WDM Immediate - 2 [] |cpu| {
+        fetch(cpu);
+    },
+
+    // XCE (Exchange Carry and Emulation Flags)
+    0xFB => XCE Implied - 2 [] |cpu| xce(cpu),
+}
+
+trait Instructions<M: Mapper> {
+    const INSTRUCTIONS: [Instruction<M>; 256];
+}
+
+impl<M: Mapper, W: Widths> Instructions<M> for W {
+    const INSTRUCTIONS: [Instruction<M>; 256] = {
+        let mut table = [instruction::<M, W>(0); 256];
+        let mut opcode = 1;
+        while opcode < 256 {
+            table[opcode] = instruction::<M, W>(opcode as u8);
+            opcode += 1;
+        }
+        table
+    };
+}
+
+/// Returns the opcode table for current register widths.
+///
+/// Tables are static, but the lifetime cannot be `'static` as mappers may
+/// borrow ROM data.
+pub fn table<'a, M: Mapper + 'a>(registers: &Registers) -> &'a [Instruction<M>; 256] {
+    if registers.emulation {
+        return &<Emulation as Instructions<M>>::INSTRUCTIONS;
+    }
+    match (registers.flags.contains(FLAG_A16), registers.flags.contains(FLAG_XY16)) {
+        (false, false) => &<A8XY8 as Instructions<M>>::INSTRUCTIONS,
+        (false, true) => &<A8XY16 as Instructions<M>>::INSTRUCTIONS,
+        (true, false) => &<A16XY8 as Instructions<M>>::INSTRUCTIONS,
+        (true, true) => &<A16XY16 as Instructions<M>>::INSTRUCTIONS,
+    }
+}
+
+pub fn run_instruction<M: Mapper>(cpu: &mut CPU<M>) {
+    let opcode = fetch(cpu);
+    let handler = table(&cpu.registers)[opcode as usize].handler;
+    handler(cpu);
+}
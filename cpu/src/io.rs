@@ -0,0 +1,327 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! CPU I/O registers at $4016-$4017 and $4200-$421F.
+
+use std::cmp;
+
+/// Number of master cycles in a scanline.
+pub const CYCLES_PER_LINE: u64 = 1364;
+
+/// Number of master cycles in a frame, 262 scanlines.
+pub const CYCLES_PER_FRAME: u64 = 262 * CYCLES_PER_LINE;
+
+/// First scanline of vertical blanking.
+pub const VBLANK_LINE: u64 = 225;
+
+/// Horizontal blanking starts at this dot, and lasts until the next line.
+pub const HBLANK_DOT: u64 = 274;
+
+/// Number of master cycles in the fastest CPU cycle.
+const FAST_CYCLE: u64 = 6;
+
+/// Interrupts enabled by NMITIMEN.
+const NMI_ENABLE: u8 = 0x80;
+const IRQ_H: u8 = 0x10;
+const IRQ_V: u8 = 0x20;
+
+pub struct Io {
+    /// Whether ROM in banks $80-$FF is accessed in 6 master cycles rather
+    /// than 8, set by writing to MEMSEL ($420D).
+    pub fast_rom: bool,
+    /// NMITIMEN ($4200), enabling the NMI, timer IRQs and joypad reading.
+    pub nmitimen: u8,
+    /// WRIO ($4201), read back through RDIO.
+    pub wrio: u8,
+    wrmpya: u8,
+    wrdiv: u16,
+    /// HTIME ($4207-$4208), dot at which the timer IRQ fires.
+    pub htime: u16,
+    /// VTIME ($4209-$420A), scanline at which the timer IRQ fires.
+    pub vtime: u16,
+    /// RDDIV ($4214-$4215), quotient of a division, or the multiplier.
+    rddiv: u16,
+    /// RDMPY ($4216-$4217), product of a multiplication, or the remainder.
+    rdmpy: u16,
+    /// Number of CPU cycles left until the multiplication finishes.
+    multiply_cycles: u8,
+    /// Number of CPU cycles left until the division finishes.
+    divide_cycles: u8,
+    /// Value added to the product or subtracted from the remainder in
+    /// the next cycle.
+    shift: u32,
+    /// Flag of RDNMI ($4210), set when vertical blanking starts.
+    pub nmi_flag: bool,
+    /// Flag of TIMEUP ($4211), which drives the IRQ line of the CPU.
+    pub timeup: bool,
+    /// NMI waiting to be taken by the CPU.
+    pub nmi: bool,
+    /// Master cycle of the last CPU cycle.
+    now: u64,
+    /// Master cycle of the next vertical blanking, frame or timer event.
+    next_event: u64,
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Io::new()
+    }
+}
+
+impl Io {
+    pub fn new() -> Self {
+        let mut io = Io {
+            fast_rom: false,
+            nmitimen: 0,
+            wrio: 0xFF,
+            wrmpya: 0xFF,
+            wrdiv: 0xFFFF,
+            htime: 0x1FF,
+            vtime: 0x1FF,
+            rddiv: 0,
+            rdmpy: 0,
+            multiply_cycles: 0,
+            divide_cycles: 0,
+            shift: 0,
+            nmi_flag: false,
+            timeup: false,
+            nmi: false,
+            now: 0,
+            next_event: 0,
+        };
+        io.next_event = io.schedule(0);
+        io
+    }
+
+    /// Resets registers, keeping track of time.
+    pub fn reset(&mut self) {
+        let now = self.now;
+        *self = Io::new();
+        self.now = now;
+        self.next_event = self.schedule(now);
+    }
+
+    /// Master cycle of the next event, which could raise an interrupt.
+    pub fn next_event(&self) -> u64 {
+        self.next_event
+    }
+
+    /// Scanline and dot at the current time.
+    pub fn position(&self) -> (u64, u64) {
+        let cycle = self.now % CYCLES_PER_FRAME;
+        (cycle / CYCLES_PER_LINE, cycle % CYCLES_PER_LINE / 4)
+    }
+
+    /// Runs CPU cycles until a given master cycle. Usually that's a single
+    /// cycle, but a halted processor can skip over many at once.
+    pub fn tick(&mut self, now: u64) {
+        let cycles = cmp::max(1, now.saturating_sub(self.now) / FAST_CYCLE);
+        self.now = now;
+        let steps = cmp::min(cycles, (self.multiply_cycles + self.divide_cycles) as u64);
+        for _ in 0..steps {
+            self.step_alu();
+        }
+        while self.next_event <= now {
+            let event = self.next_event;
+            self.fire(event);
+            self.next_event = self.schedule(event);
+        }
+    }
+
+    /// Runs a cycle of the multiplication or division unit.
+    fn step_alu(&mut self) {
+        if self.multiply_cycles != 0 {
+            self.multiply_cycles -= 1;
+            if self.rddiv & 1 != 0 {
+                self.rdmpy = self.rdmpy.wrapping_add(self.shift as u16);
+            }
+            self.rddiv >>= 1;
+            self.shift <<= 1;
+        }
+        if self.divide_cycles != 0 {
+            self.divide_cycles -= 1;
+            self.rddiv <<= 1;
+            self.shift >>= 1;
+            if self.rdmpy as u32 >= self.shift {
+                self.rdmpy -= self.shift as u16;
+                self.rddiv |= 1;
+            }
+        }
+    }
+
+    /// Master cycle of the timer IRQ in a frame, if enabled.
+    fn timer(&self) -> Option<u64> {
+        let line = if self.nmitimen & IRQ_V != 0 {
+            self.vtime as u64
+        } else {
+            0
+        };
+        let dot = if self.nmitimen & IRQ_H != 0 {
+            self.htime as u64
+        } else {
+            0
+        };
+        if self.nmitimen & (IRQ_H | IRQ_V) == 0 || line >= CYCLES_PER_FRAME / CYCLES_PER_LINE ||
+           dot >= CYCLES_PER_LINE / 4 {
+            return None;
+        }
+        Some(line * CYCLES_PER_LINE + dot * 4)
+    }
+
+    /// Finds the first event after a given master cycle.
+    fn schedule(&self, after: u64) -> u64 {
+        let frame = after - after % CYCLES_PER_FRAME;
+        let vblank = repeat_after(after, frame + VBLANK_LINE * CYCLES_PER_LINE, CYCLES_PER_FRAME);
+        let next = cmp::min(frame + CYCLES_PER_FRAME, vblank);
+        match self.timer() {
+            // H-IRQ fires on every scanline
+            Some(timer) if self.nmitimen & IRQ_V == 0 => {
+                let line = after - after % CYCLES_PER_LINE;
+                cmp::min(next, repeat_after(after, line + timer, CYCLES_PER_LINE))
+            }
+            Some(timer) => cmp::min(next, repeat_after(after, frame + timer, CYCLES_PER_FRAME)),
+            None => next,
+        }
+    }
+
+    fn fire(&mut self, event: u64) {
+        let cycle = event % CYCLES_PER_FRAME;
+        if cycle == 0 {
+            self.nmi_flag = false;
+        }
+        if cycle == VBLANK_LINE * CYCLES_PER_LINE {
+            self.nmi_flag = true;
+            if self.nmitimen & NMI_ENABLE != 0 {
+                self.nmi = true;
+            }
+        }
+        if let Some(timer) = self.timer() {
+            let matches = if self.nmitimen & IRQ_V == 0 {
+                cycle % CYCLES_PER_LINE == timer
+            } else {
+                cycle == timer
+            };
+            if matches {
+                self.timeup = true;
+            }
+        }
+    }
+
+    /// Reads a register. Bits which aren't driven by anything come from
+    /// `open_bus`.
+    pub fn read(&mut self, address: u16, open_bus: u8) -> u8 {
+        match address {
+            // JOYSER0, JOYSER1
+            0x4016 => open_bus & 0xFC,
+            0x4017 => open_bus & 0xE0 | 0x1C,
+            // RDNMI, with CPU version 2
+            0x4210 => {
+                let flag = if self.nmi_flag { 0x80 } else { 0 };
+                self.nmi_flag = false;
+                flag | open_bus & 0x70 | 0x02
+            }
+            // TIMEUP
+            0x4211 => {
+                let flag = if self.timeup { 0x80 } else { 0 };
+                self.timeup = false;
+                flag | open_bus & 0x7F
+            }
+            // HVBJOY
+            0x4212 => {
+                let (line, dot) = self.position();
+                let vblank = if line >= VBLANK_LINE { 0x80 } else { 0 };
+                let hblank = if dot >= HBLANK_DOT || dot == 0 { 0x40 } else { 0 };
+                vblank | hblank | open_bus & 0x3E
+            }
+            // RDIO
+            0x4213 => self.wrio,
+            0x4214 => self.rddiv as u8,
+            0x4215 => (self.rddiv >> 8) as u8,
+            0x4216 => self.rdmpy as u8,
+            0x4217 => (self.rdmpy >> 8) as u8,
+            0x4218...0x421F => 0,
+            // Write-only registers
+            _ => open_bus,
+        }
+    }
+
+    /// Writes a register. Writes to registers which aren't emulated are
+    /// ignored.
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4200 => {
+                // Enabling NMI during vertical blanking fires it immediately
+                if value & NMI_ENABLE != 0 && self.nmitimen & NMI_ENABLE == 0 && self.nmi_flag {
+                    self.nmi = true;
+                }
+                self.nmitimen = value;
+                if value & (IRQ_H | IRQ_V) == 0 {
+                    self.timeup = false;
+                }
+                self.next_event = self.schedule(self.now);
+            }
+            0x4201 => self.wrio = value,
+            0x4202 => self.wrmpya = value,
+            0x4203 => {
+                self.rdmpy = 0;
+                if self.busy() {
+                    return;
+                }
+                self.rddiv = (value as u16) << 8 | self.wrmpya as u16;
+                self.shift = value as u32;
+                self.multiply_cycles = 8;
+            }
+            0x4204 => self.wrdiv = self.wrdiv & 0xFF00 | value as u16,
+            0x4205 => self.wrdiv = self.wrdiv & 0x00FF | (value as u16) << 8,
+            0x4206 => {
+                self.rdmpy = self.wrdiv;
+                if self.busy() {
+                    return;
+                }
+                self.shift = (value as u32) << 16;
+                self.divide_cycles = 16;
+            }
+            0x4207 => self.set_timer(|io| &mut io.htime, value as u16, 0x100),
+            0x4208 => self.set_timer(|io| &mut io.htime, (value as u16 & 1) << 8, 0xFF),
+            0x4209 => self.set_timer(|io| &mut io.vtime, value as u16, 0x100),
+            0x420A => self.set_timer(|io| &mut io.vtime, (value as u16 & 1) << 8, 0xFF),
+            0x420D => self.fast_rom = value & 1 != 0,
+            _ => {}
+        }
+    }
+
+    /// Whether the multiplication or division unit is busy.
+    fn busy(&self) -> bool {
+        self.multiply_cycles != 0 || self.divide_cycles != 0
+    }
+
+    /// Replaces bits of HTIME or VTIME outside of `keep`.
+    fn set_timer(&mut self, timer: fn(&mut Io) -> &mut u16, value: u16, keep: u16) {
+        let timer = timer(self);
+        *timer = *timer & keep | value;
+        self.next_event = self.schedule(self.now);
+    }
+}
+
+/// First time an event repeating every `period` happens after a given
+/// master cycle, with `start` being its time in the current period.
+fn repeat_after(after: u64, start: u64, period: u64) -> u64 {
+    if start > after {
+        start
+    } else {
+        start + period
+    }
+}
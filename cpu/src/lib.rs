@@ -18,34 +18,62 @@
 extern crate bitflags;
 
 mod bitwidth;
+#[cfg(feature = "block-cache")]
+mod blocks;
 pub mod buffer;
+pub mod bus;
 pub mod cpu;
+pub mod dma;
+pub mod error;
 mod instructions;
+pub mod io;
 pub mod mapper;
+pub mod opcodes;
+pub mod ppu;
+mod render;
 
 use cpu::CPU;
+use error::{Error, ErrorPolicy};
+use io::CYCLES_PER_FRAME;
 use mapper::Mapper;
 
 pub struct Emulator<M: Mapper> {
     cpu: CPU<M>,
+    /// Master cycle at which the current frame ends.
+    frame_end: u64,
 }
 
 impl<M: Mapper> Emulator<M> {
     pub fn from_rom(mapper: M) -> Emulator<M> {
-        Emulator { cpu: CPU::new(mapper) }
+        Emulator {
+            cpu: CPU::new(mapper),
+            frame_end: 0,
+        }
     }
 
     pub fn game_title(&self) -> [u8; 21] {
         let mut out = [0; 21];
         for (i, out_byte) in out.iter_mut().enumerate() {
-            *out_byte = self.cpu.read(0x00, 0xFFC0 + i as u16);
+            *out_byte = self.cpu.peek(0x00, 0xFFC0 + i as u16);
         }
         out
     }
 
-    pub fn run_frame(&mut self, buffer: &mut [u32; buffer::WIDTH * buffer::HEIGHT]) {
-        for _ in 0..1_000_000 {
-            self.cpu.step();
-        }
+    /// Decides what happens when the emulated program does something
+    /// unsupported. By default `run_frame` returns an error.
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.cpu.error_policy = policy;
+    }
+
+    /// Runs until the end of a frame, and draws the last finished frame
+    /// into `buffer`. Dots cover 2×2 pixels, except in the high resolution
+    /// modes where they're one pixel wide.
+    pub fn run_frame(&mut self,
+                     buffer: &mut [u32; buffer::WIDTH * buffer::HEIGHT])
+                     -> Result<(), Error> {
+        self.frame_end += CYCLES_PER_FRAME;
+        self.cpu.run_until(self.frame_end)?;
+        self.cpu.bus.draw_frame(buffer);
+        Ok(())
     }
 }
@@ -14,29 +14,68 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use error::Error;
+
 pub trait Mapper {
-    fn read(&self, bank: u8, address: u16) -> u8;
-    fn write(&mut self, bank: u8, address: u16, value: u8);
+    /// Reads a byte, or returns `None` if nothing on the cartridge is
+    /// mapped to an address.
+    fn read(&self, bank: u8, address: u16) -> Option<u8>;
+    fn write(&mut self, bank: u8, address: u16, value: u8) -> Result<(), Error>;
 }
 
 pub struct LoROM<'a> {
     rom: &'a [u8],
+    /// Battery backed save RAM, sized by the cartridge header.
+    pub sram: Vec<u8>,
 }
 
 impl<'a> LoROM<'a> {
     pub fn new(rom: &[u8]) -> LoROM {
-        LoROM { rom: rom }
+        // The header stores the save RAM size as a power of two in KiB
+        let sram_size = match rom.get(0x7FD8) {
+            Some(&size @ 1...8) => 0x400 << size,
+            _ => 0,
+        };
+        LoROM {
+            rom,
+            sram: vec![0; sram_size],
+        }
+    }
+
+    /// Finds the save RAM byte at an address, which is mirrored through
+    /// the lower halves of banks $70-$7D.
+    fn sram_index(&self, bank: u8, address: u16) -> Option<usize> {
+        match bank & 0x7F {
+            0x70...0x7D if address < 0x8000 && !self.sram.is_empty() => {
+                let index = (bank & 0xF) as usize * 0x8000 + address as usize;
+                Some(index % self.sram.len())
+            }
+            _ => None,
+        }
     }
 }
 
 impl<'a> Mapper for LoROM<'a> {
-    fn read(&self, bank: u8, address: u16) -> u8 {
-        self.rom[bank as usize * 0x8000 + (address as usize & 0x7FFF)]
+    fn read(&self, bank: u8, address: u16) -> Option<u8> {
+        if let Some(index) = self.sram_index(bank, address) {
+            return Some(self.sram[index]);
+        }
+        // Banks $00-$3F only have ROM in the upper half
+        if bank & 0x40 == 0 && address < 0x8000 {
+            return None;
+        }
+        // Banks $80-$FF mirror banks $00-$7F
+        let index = (bank & 0x7F) as usize * 0x8000 + (address as usize & 0x7FFF);
+        self.rom.get(index).cloned()
     }
 
-    fn write(&mut self, bank: u8, address: u16, value: u8) {
-        println!("{:02x}:{:04x} = {:02x}", bank, address, value);
-        // TODO: Save RAM support
-        unimplemented!();
+    fn write(&mut self, bank: u8, address: u16, value: u8) -> Result<(), Error> {
+        match self.sram_index(bank, address) {
+            Some(index) => {
+                self.sram[index] = value;
+                Ok(())
+            }
+            None => Err(Error::unmapped_write(bank, address, value)),
+        }
     }
 }
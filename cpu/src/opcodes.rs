@@ -0,0 +1,173 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Opcode table, shared by the executor, the disassembler and the tracer.
+
+use cpu::CPU;
+use mapper::Mapper;
+
+pub use instructions::table;
+
+/// How an instruction finds its operand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    AbsoluteLong,
+    AbsoluteLongX,
+    AbsoluteIndirect,
+    AbsoluteXIndirect,
+    AbsoluteIndirectLong,
+    Direct,
+    DirectX,
+    DirectY,
+    DirectIndirect,
+    DirectIndirectLong,
+    DirectXIndirect,
+    DirectIndirectY,
+    DirectIndirectLongY,
+    StackRelative,
+    StackRelativeIndirectY,
+    Relative,
+    RelativeLong,
+    BlockMove,
+}
+
+/// Register which decides the operand size of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    None,
+    Accumulator,
+    Index,
+}
+
+/// Entry of an opcode table.
+///
+/// There is a separate table for every combination of register widths, so
+/// the details here are for a specific width.
+pub struct Instruction<M: Mapper> {
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    pub width: Width,
+    /// Whether the register deciding the operand size is 16-bit.
+    pub sixteen_bits: bool,
+    /// Number of CPU cycles, assuming the low byte of the direct page
+    /// register is zero, indexing doesn't cross a page, branches aren't
+    /// taken and block moves copy a single byte.
+    pub cycles: u8,
+    pub handler: fn(&mut CPU<M>),
+}
+
+impl<M: Mapper> Clone for Instruction<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M: Mapper> Copy for Instruction<M> {}
+
+impl<M: Mapper> Instruction<M> {
+    /// Length of an instruction in bytes, including the opcode.
+    pub fn length(&self) -> u16 {
+        use self::AddressingMode::*;
+        1 + match self.mode {
+            Implied | Accumulator => 0,
+            Immediate => if self.sixteen_bits { 2 } else { 1 },
+            Direct | DirectX | DirectY | DirectIndirect | DirectIndirectLong |
+            DirectXIndirect | DirectIndirectY | DirectIndirectLongY | StackRelative |
+            StackRelativeIndirectY | Relative => 1,
+            Absolute | AbsoluteX | AbsoluteY | AbsoluteIndirect | AbsoluteXIndirect |
+            AbsoluteIndirectLong | RelativeLong | BlockMove => 2,
+            AbsoluteLong | AbsoluteLongX => 3,
+        }
+    }
+}
+
+/// Disassembles an instruction, assuming current register widths.
+///
+/// # Example
+///
+/// ```
+/// use snesemu_cpu::cpu::CPU;
+/// use snesemu_cpu::mapper::LoROM;
+/// use snesemu_cpu::opcodes::disassemble;
+///
+/// let mut rom = [0; 0x8000];
+/// rom[..3].copy_from_slice(&[0xBD, 0x34, 0x12]);
+/// let cpu = CPU::new(LoROM::new(&rom));
+/// assert_eq!("LDA $1234,X", disassemble(&cpu, 0x00, 0x8000));
+/// ```
+pub fn disassemble<M: Mapper>(cpu: &CPU<M>, bank: u8, address: u16) -> String {
+    use self::AddressingMode::*;
+    let byte = |offset| cpu.peek(bank, address.wrapping_add(offset));
+    let instruction = &table::<M>(&cpu.registers)[byte(0) as usize];
+    let word = byte(1) as u16 | (byte(2) as u16) << 8;
+    let long = word as u32 | (byte(3) as u32) << 16;
+    let next = address.wrapping_add(instruction.length());
+    let operand = match instruction.mode {
+        Implied => return instruction.mnemonic.to_string(),
+        Accumulator => "A".to_string(),
+        Immediate if instruction.sixteen_bits => format!("#${:04X}", word),
+        Immediate => format!("#${:02X}", byte(1)),
+        Absolute => format!("${:04X}", word),
+        AbsoluteX => format!("${:04X},X", word),
+        AbsoluteY => format!("${:04X},Y", word),
+        AbsoluteLong => format!("${:06X}", long),
+        AbsoluteLongX => format!("${:06X},X", long),
+        AbsoluteIndirect => format!("(${:04X})", word),
+        AbsoluteXIndirect => format!("(${:04X},X)", word),
+        AbsoluteIndirectLong => format!("[${:04X}]", word),
+        Direct => format!("${:02X}", byte(1)),
+        DirectX => format!("${:02X},X", byte(1)),
+        DirectY => format!("${:02X},Y", byte(1)),
+        DirectIndirect => format!("(${:02X})", byte(1)),
+        DirectIndirectLong => format!("[${:02X}]", byte(1)),
+        DirectXIndirect => format!("(${:02X},X)", byte(1)),
+        DirectIndirectY => format!("(${:02X}),Y", byte(1)),
+        DirectIndirectLongY => format!("[${:02X}],Y", byte(1)),
+        StackRelative => format!("${:02X},S", byte(1)),
+        StackRelativeIndirectY => format!("(${:02X},S),Y", byte(1)),
+        Relative => format!("${:04X}", next.wrapping_add(byte(1) as i8 as u16)),
+        RelativeLong => format!("${:04X}", next.wrapping_add(word)),
+        // Operands are stored as destination bank followed by source bank,
+        // but written the other way around.
+        BlockMove => format!("${:02X},${:02X}", byte(2), byte(1)),
+    };
+    format!("{} {}", instruction.mnemonic, operand)
+}
+
+/// Describes the next instruction to run along with the register state,
+/// for trace logs.
+pub fn trace<M: Mapper>(cpu: &CPU<M>) -> String {
+    let registers = &cpu.registers;
+    format!("{:02X}:{:04X} {:<16} A:{:04X} X:{:04X} Y:{:04X} S:{:04X} D:{:04X} DB:{:02X} P:{:02X} \
+             E:{}",
+            registers.pb,
+            registers.pc,
+            disassemble(cpu, registers.pb, registers.pc),
+            registers.a,
+            registers.x,
+            registers.y,
+            registers.sp,
+            registers.dp,
+            registers.db,
+            registers.status(),
+            registers.emulation as u8)
+}
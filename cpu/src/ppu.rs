@@ -0,0 +1,573 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Picture processing unit, at $2100-$213F on the B-bus.
+//!
+//! It is made of two chips, PPU1 and PPU2. Each of them remembers the last
+//! value read from its registers, and returns it for bits it doesn't
+//! drive, independently of the open bus of the CPU.
+//!
+//! Video memory, palette memory and sprite attributes are accessed through
+//! ports which auto-increment their addresses. VRAM and OAM are busy
+//! drawing the picture during active display, so writes to them only go
+//! through during vertical blanking or forced blanking.
+//!
+//! Scanlines are drawn once they're finished, whenever the PPU is accessed
+//! or the frame is copied out, so writes in the middle of a frame affect
+//! the following lines.
+
+use buffer;
+use bus::Device;
+use io::{HBLANK_DOT, VBLANK_LINE};
+use render;
+
+/// Version of PPU1, read from STAT77.
+const PPU1_VERSION: u8 = 1;
+
+/// Version of PPU2, read from STAT78.
+const PPU2_VERSION: u8 = 3;
+
+/// Number of 16-bit words in VRAM.
+pub const VRAM_WORDS: usize = 0x8000;
+
+/// Number of colors in CGRAM.
+pub const CGRAM_COLORS: usize = 0x100;
+
+/// Number of bytes in OAM, a low table of 512 bytes and a high table of
+/// 32 bytes.
+pub const OAM_SIZE: usize = 0x220;
+
+/// Row of the frame buffer where the first scanline goes. Every dot covers
+/// 2×2 pixels, with 224 lines centered in 240.
+const FIRST_ROW: usize = 16;
+
+/// Bit of INIDISP, turning off the display.
+const FORCE_BLANK: u8 = 0x80;
+
+/// Bit of VMAIN, incrementing the address after accessing the high byte
+/// rather than the low one.
+const INCREMENT_HIGH: u8 = 0x80;
+
+pub struct Ppu {
+    /// Last value read from PPU1 registers.
+    pub ppu1_open_bus: u8,
+    /// Last value read from PPU2 registers.
+    pub ppu2_open_bus: u8,
+    /// Video memory, holding tiles and tilemaps.
+    pub vram: Vec<u16>,
+    /// Palette memory, with colors in BGR555.
+    pub cgram: Vec<u16>,
+    /// Object attribute memory, describing sprites.
+    pub oam: Vec<u8>,
+    /// INIDISP ($2100), forced blanking and brightness.
+    pub inidisp: u8,
+    /// OBSEL ($2101), sprite sizes and tile addresses.
+    pub obsel: u8,
+    /// OAMADDL/H ($2102-$2103), word address in OAM and the priority
+    /// rotation bit, reloaded into `oam_address`.
+    pub oamadd: u16,
+    /// Byte address in OAM accessed through OAMDATA.
+    pub oam_address: u16,
+    /// Even byte written to the low table of OAM, waiting for the odd one.
+    oam_latch: u8,
+    /// BGMODE ($2105), background mode and tile sizes.
+    pub bgmode: u8,
+    /// MOSAIC ($2106).
+    pub mosaic: u8,
+    /// BG1SC-BG4SC ($2107-$210A), tilemap addresses and sizes.
+    pub bgsc: [u8; 4],
+    /// BG12NBA and BG34NBA ($210B-$210C), tile addresses of backgrounds.
+    pub bgnba: [u8; 2],
+    /// BG1HOFS-BG4HOFS, 10-bit horizontal scroll of backgrounds.
+    pub hofs: [u16; 4],
+    /// BG1VOFS-BG4VOFS, 10-bit vertical scroll of backgrounds.
+    pub vofs: [u16; 4],
+    /// Previous value written to any scroll register ($210D-$2114), which
+    /// are written twice.
+    scroll_latch: u8,
+    /// M7HOFS and M7VOFS, written together with BG1HOFS and BG1VOFS,
+    /// 13-bit signed scroll of the mode 7 background.
+    pub m7hofs: i16,
+    pub m7vofs: i16,
+    /// M7SEL ($211A), flipping and what's outside of the mode 7 tilemap.
+    pub m7sel: u8,
+    /// M7A-M7D ($211B-$211E), signed 8.8 fixed point matrix of mode 7.
+    pub m7a: i16,
+    pub m7b: i16,
+    pub m7c: i16,
+    pub m7d: i16,
+    /// M7X and M7Y ($211F-$2120), 13-bit signed center of rotation.
+    pub m7x: i16,
+    pub m7y: i16,
+    /// Previous value written to any mode 7 register, which are written
+    /// twice.
+    m7_latch: u8,
+    /// VMAIN ($2115), how the VRAM address is remapped and incremented.
+    pub vmain: u8,
+    /// VMADDL/H ($2116-$2117), word address in VRAM.
+    pub vram_address: u16,
+    /// Word prefetched from VRAM, read through VMDATALREAD and
+    /// VMDATAHREAD.
+    vram_latch: u16,
+    /// CGADD ($2121), address of a color in CGRAM.
+    pub cgram_address: u8,
+    /// Low byte of a color written to CGDATA, waiting for the high one.
+    cgram_latch: u8,
+    /// Whether the next access to CGRAM is to the high byte of a color.
+    cgram_high: bool,
+    /// W12SEL, W34SEL and WOBJSEL ($2123-$2125), window settings of layers.
+    pub window_select: [u8; 3],
+    /// WH0-WH3 ($2126-$2129), left and right edges of both windows.
+    pub window_edges: [u8; 4],
+    /// WBGLOG and WOBJLOG ($212A-$212B), how the windows are combined.
+    pub window_logic: [u8; 2],
+    /// TM ($212C), layers on the main screen.
+    pub tm: u8,
+    /// TS ($212D), layers on the subscreen.
+    pub ts: u8,
+    /// TMW ($212E), layers masked by windows on the main screen.
+    pub tmw: u8,
+    /// TSW ($212F), layers masked by windows on the subscreen.
+    pub tsw: u8,
+    /// CGWSEL ($2130), color math settings.
+    pub cgwsel: u8,
+    /// CGADSUB ($2131), color math operation and layers.
+    pub cgadsub: u8,
+    /// Fixed color set through COLDATA ($2132), in BGR555.
+    pub fixed_color: u16,
+    /// SETINI ($2133), screen mode settings.
+    pub setini: u8,
+    /// OPHCT ($213C), dot latched through SLHV.
+    pub ophct: u16,
+    /// OPVCT ($213D), scanline latched through SLHV.
+    pub opvct: u16,
+    /// Whether the counters were latched since STAT78 was last read.
+    counters_latched: bool,
+    /// Whether the next reads of OPHCT and OPVCT return their high bytes.
+    ophct_high: bool,
+    opvct_high: bool,
+    /// Current scanline and dot.
+    line: u64,
+    dot: u64,
+    /// Picture drawn so far, in the layout of the rendering buffer.
+    frame: Vec<u32>,
+    /// Next scanline to draw. Scanline 0 is never displayed.
+    next_line: u64,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            ppu1_open_bus: 0,
+            ppu2_open_bus: 0,
+            vram: vec![0; VRAM_WORDS],
+            cgram: vec![0; CGRAM_COLORS],
+            oam: vec![0; OAM_SIZE],
+            inidisp: FORCE_BLANK,
+            obsel: 0,
+            oamadd: 0,
+            oam_address: 0,
+            oam_latch: 0,
+            bgmode: 0,
+            mosaic: 0,
+            bgsc: [0; 4],
+            bgnba: [0; 2],
+            hofs: [0; 4],
+            vofs: [0; 4],
+            scroll_latch: 0,
+            m7hofs: 0,
+            m7vofs: 0,
+            m7sel: 0,
+            m7a: 0,
+            m7b: 0,
+            m7c: 0,
+            m7d: 0,
+            m7x: 0,
+            m7y: 0,
+            m7_latch: 0,
+            vmain: 0,
+            vram_address: 0,
+            vram_latch: 0,
+            cgram_address: 0,
+            cgram_latch: 0,
+            cgram_high: false,
+            window_select: [0; 3],
+            window_edges: [0; 4],
+            window_logic: [0; 2],
+            tm: 0,
+            ts: 0,
+            tmw: 0,
+            tsw: 0,
+            cgwsel: 0,
+            cgadsub: 0,
+            fixed_color: 0,
+            setini: 0,
+            ophct: 0,
+            opvct: 0,
+            counters_latched: false,
+            ophct_high: false,
+            opvct_high: false,
+            line: 0,
+            dot: 0,
+            frame: vec![0; buffer::WIDTH * buffer::HEIGHT],
+            next_line: 1,
+        }
+    }
+
+    /// Draws scanlines finished by a given scanline and dot. Going back
+    /// to an earlier scanline finishes the frame and starts a new one.
+    fn catch_up(&mut self, line: u64, dot: u64) {
+        let finished = if dot >= HBLANK_DOT { line + 1 } else { line };
+        if finished < self.next_line {
+            self.draw_lines(VBLANK_LINE);
+            self.next_line = 1;
+        }
+        self.draw_lines(finished.min(VBLANK_LINE));
+    }
+
+    fn draw_lines(&mut self, end: u64) {
+        let mut dots = [0; buffer::WIDTH];
+        while self.next_line < end {
+            render::line(self, self.next_line as u16, &mut dots);
+            let row = FIRST_ROW + (self.next_line as usize - 1) * 2;
+            for row in row..row + 2 {
+                self.frame[row * buffer::WIDTH..(row + 1) * buffer::WIDTH].copy_from_slice(&dots);
+            }
+            self.next_line += 1;
+        }
+    }
+
+    /// Whether the picture is being drawn, keeping VRAM and OAM busy.
+    fn active_display(&self) -> bool {
+        self.inidisp & FORCE_BLANK == 0 && self.line < VBLANK_LINE
+    }
+
+    /// Word address in VRAM accessed through the data ports, after
+    /// remapping by bits 2-3 of VMAIN. Remapping rotates the low 8, 9 or
+    /// 10 bits of the address left by 3, which helps with writing bitmaps
+    /// as tiles.
+    fn vram_index(&self) -> usize {
+        let address = self.vram_address;
+        let address = match self.vmain >> 2 & 3 {
+            0 => address,
+            1 => address & 0xFF00 | (address & 0x001F) << 3 | address >> 5 & 7,
+            2 => address & 0xFE00 | (address & 0x003F) << 3 | address >> 6 & 7,
+            _ => address & 0xFC00 | (address & 0x007F) << 3 | address >> 7 & 7,
+        };
+        address as usize & (VRAM_WORDS - 1)
+    }
+
+    /// Advances the VRAM address by 1, 32 or 128 words.
+    fn increment_vram_address(&mut self) {
+        let step = match self.vmain & 3 {
+            0 => 1,
+            1 => 32,
+            _ => 128,
+        };
+        self.vram_address = self.vram_address.wrapping_add(step);
+    }
+
+    /// Writes a byte of VRAM through VMDATAL or VMDATAH.
+    fn write_vram(&mut self, high: bool, value: u8) {
+        if !self.active_display() {
+            let index = self.vram_index();
+            let word = &mut self.vram[index];
+            *word = if high {
+                *word & 0x00FF | (value as u16) << 8
+            } else {
+                *word & 0xFF00 | value as u16
+            };
+        }
+        if high == (self.vmain & INCREMENT_HIGH != 0) {
+            self.increment_vram_address();
+        }
+    }
+
+    /// Reads a byte of VRAM through VMDATALREAD or VMDATAHREAD. The value
+    /// comes from the prefetch latch, which is refilled before the address
+    /// increments.
+    fn read_vram(&mut self, high: bool) -> u8 {
+        let value = if high {
+            (self.vram_latch >> 8) as u8
+        } else {
+            self.vram_latch as u8
+        };
+        if high == (self.vmain & INCREMENT_HIGH != 0) {
+            self.vram_latch = self.vram[self.vram_index()];
+            self.increment_vram_address();
+        }
+        value
+    }
+
+    /// Writes a byte of OAM through OAMDATA. Bytes of the low table are
+    /// written in pairs, when the odd byte arrives.
+    fn write_oam(&mut self, value: u8) {
+        let address = self.oam_address as usize;
+        if address & 1 == 0 {
+            self.oam_latch = value;
+        }
+        if !self.active_display() {
+            if address >= 0x200 {
+                self.oam[0x200 | address & 0x1F] = value;
+            } else if address & 1 != 0 {
+                self.oam[address - 1] = self.oam_latch;
+                self.oam[address] = value;
+            }
+        }
+        self.oam_address = (self.oam_address + 1) & 0x3FF;
+    }
+
+    fn read_oam(&mut self) -> u8 {
+        let address = self.oam_address as usize;
+        let value = if address >= 0x200 {
+            self.oam[0x200 | address & 0x1F]
+        } else {
+            self.oam[address]
+        };
+        self.oam_address = (self.oam_address + 1) & 0x3FF;
+        value
+    }
+
+    /// Writes a byte of a color through CGDATA. The color is written once
+    /// both bytes arrive.
+    fn write_cgram(&mut self, value: u8) {
+        if self.cgram_high {
+            let color = (value as u16 & 0x7F) << 8 | self.cgram_latch as u16;
+            self.cgram[self.cgram_address as usize] = color;
+            self.cgram_address = self.cgram_address.wrapping_add(1);
+        } else {
+            self.cgram_latch = value;
+        }
+        self.cgram_high = !self.cgram_high;
+    }
+
+    fn read_cgram(&mut self) -> u8 {
+        let color = self.cgram[self.cgram_address as usize];
+        self.cgram_high = !self.cgram_high;
+        if self.cgram_high {
+            color as u8
+        } else {
+            self.cgram_address = self.cgram_address.wrapping_add(1);
+            (color >> 8) as u8 & 0x7F | self.ppu2_open_bus & 0x80
+        }
+    }
+
+    /// Writes BGnHOFS, which mixes in the low 3 bits of the previous
+    /// write to any scroll register, along with bits of its own.
+    fn write_hofs(&mut self, layer: usize, value: u8) {
+        let hofs = &mut self.hofs[layer];
+        *hofs = ((value as u16) << 8 | (self.scroll_latch & !7) as u16 | *hofs >> 8 & 7) & 0x3FF;
+        self.scroll_latch = value;
+    }
+
+    /// Writes BGnVOFS, taking the low byte from the previous write to any
+    /// scroll register.
+    fn write_vofs(&mut self, layer: usize, value: u8) {
+        self.vofs[layer] = ((value as u16) << 8 | self.scroll_latch as u16) & 0x3FF;
+        self.scroll_latch = value;
+    }
+
+    /// Combines a write to a mode 7 register with the previous one, into
+    /// a 16-bit value.
+    fn write_m7(&mut self, value: u8) -> i16 {
+        let word = (value as u16) << 8 | self.m7_latch as u16;
+        self.m7_latch = value;
+        word as i16
+    }
+
+    /// Signed product of M7A and the high byte of M7B, read through MPYL,
+    /// MPYM and MPYH.
+    pub fn product(&self) -> i32 {
+        self.m7a as i32 * (self.m7b >> 8) as i32
+    }
+
+    /// Writes COLDATA, setting components of the fixed color selected by
+    /// bits 5-7.
+    fn write_coldata(&mut self, value: u8) {
+        let intensity = value as u16 & 0x1F;
+        for component in 0..3 {
+            if value & 0x20 << component != 0 {
+                let shift = component * 5;
+                self.fixed_color = self.fixed_color & !(0x1F << shift) | intensity << shift;
+            }
+        }
+    }
+
+    /// Latches the current dot and scanline into OPHCT and OPVCT.
+    fn latch_counters(&mut self) {
+        self.ophct = self.dot as u16;
+        self.opvct = self.line as u16;
+        self.counters_latched = true;
+    }
+
+    /// Reads OPHCT or OPVCT, which return their low byte, then the high
+    /// bit along with open bus of PPU2.
+    fn read_counter(counter: u16, high: &mut bool, open_bus: u8) -> u8 {
+        *high = !*high;
+        if *high {
+            counter as u8
+        } else {
+            (counter >> 8) as u8 & 1 | open_bus & 0xFE
+        }
+    }
+}
+
+/// Extends the sign of a 13-bit value.
+fn sign_extend_13(value: i16) -> i16 {
+    value << 3 >> 3
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu::new()
+    }
+}
+
+impl Device for Ppu {
+    fn read(&mut self, address: u8, open_bus: u8) -> u8 {
+        match address {
+            // Write-only registers inside of PPU1
+            0x04...0x06 | 0x08...0x0A | 0x14...0x16 | 0x18...0x1A | 0x24...0x26 |
+            0x28...0x2A => self.ppu1_open_bus,
+            // MPYL, MPYM, MPYH
+            0x34...0x36 => {
+                self.ppu1_open_bus = (self.product() >> ((address - 0x34) * 8)) as u8;
+                self.ppu1_open_bus
+            }
+            // SLHV
+            0x37 => {
+                self.latch_counters();
+                open_bus
+            }
+            // OAMDATAREAD
+            0x38 => {
+                self.ppu1_open_bus = self.read_oam();
+                self.ppu1_open_bus
+            }
+            // VMDATALREAD, VMDATAHREAD
+            0x39 | 0x3A => {
+                self.ppu1_open_bus = self.read_vram(address == 0x3A);
+                self.ppu1_open_bus
+            }
+            // RDCGRAM
+            0x3B => {
+                self.ppu2_open_bus = self.read_cgram();
+                self.ppu2_open_bus
+            }
+            // OPHCT
+            0x3C => {
+                self.ppu2_open_bus =
+                    Ppu::read_counter(self.ophct, &mut self.ophct_high, self.ppu2_open_bus);
+                self.ppu2_open_bus
+            }
+            // OPVCT
+            0x3D => {
+                self.ppu2_open_bus =
+                    Ppu::read_counter(self.opvct, &mut self.opvct_high, self.ppu2_open_bus);
+                self.ppu2_open_bus
+            }
+            // STAT77
+            0x3E => {
+                self.ppu1_open_bus = self.ppu1_open_bus & 0x10 | PPU1_VERSION;
+                self.ppu1_open_bus
+            }
+            // STAT78, which also resets the OPHCT and OPVCT flip-flops
+            0x3F => {
+                let latched = if self.counters_latched { 0x40 } else { 0 };
+                self.counters_latched = false;
+                self.ophct_high = false;
+                self.opvct_high = false;
+                self.ppu2_open_bus = latched | self.ppu2_open_bus & 0x20 | PPU2_VERSION;
+                self.ppu2_open_bus
+            }
+            _ => open_bus,
+        }
+    }
+
+    fn write(&mut self, address: u8, value: u8) {
+        match address {
+            0x00 => self.inidisp = value,
+            0x01 => self.obsel = value,
+            0x02 | 0x03 => {
+                self.oamadd = if address == 0x02 {
+                    self.oamadd & 0x8100 | value as u16
+                } else {
+                    self.oamadd & 0x00FF | (value as u16 & 0x81) << 8
+                };
+                self.oam_address = (self.oamadd & 0x1FF) << 1;
+            }
+            0x04 => self.write_oam(value),
+            0x05 => self.bgmode = value,
+            0x06 => self.mosaic = value,
+            0x07...0x0A => self.bgsc[address as usize - 0x07] = value,
+            0x0B | 0x0C => self.bgnba[address as usize - 0x0B] = value,
+            0x0D => {
+                self.write_hofs(0, value);
+                self.m7hofs = sign_extend_13(self.write_m7(value));
+            }
+            0x0E => {
+                self.write_vofs(0, value);
+                self.m7vofs = sign_extend_13(self.write_m7(value));
+            }
+            0x0F | 0x11 | 0x13 => self.write_hofs((address as usize - 0x0D) / 2, value),
+            0x10 | 0x12 | 0x14 => self.write_vofs((address as usize - 0x0E) / 2, value),
+            0x15 => self.vmain = value,
+            0x16 | 0x17 => {
+                self.vram_address = if address == 0x16 {
+                    self.vram_address & 0xFF00 | value as u16
+                } else {
+                    self.vram_address & 0x00FF | (value as u16) << 8
+                };
+                self.vram_latch = self.vram[self.vram_index()];
+            }
+            0x18 | 0x19 => self.write_vram(address == 0x19, value),
+            0x1A => self.m7sel = value,
+            0x1B => self.m7a = self.write_m7(value),
+            0x1C => self.m7b = self.write_m7(value),
+            0x1D => self.m7c = self.write_m7(value),
+            0x1E => self.m7d = self.write_m7(value),
+            0x1F => self.m7x = sign_extend_13(self.write_m7(value)),
+            0x20 => self.m7y = sign_extend_13(self.write_m7(value)),
+            0x21 => {
+                self.cgram_address = value;
+                self.cgram_high = false;
+            }
+            0x22 => self.write_cgram(value),
+            0x23...0x25 => self.window_select[address as usize - 0x23] = value,
+            0x26...0x29 => self.window_edges[address as usize - 0x26] = value,
+            0x2A | 0x2B => self.window_logic[address as usize - 0x2A] = value,
+            0x2C => self.tm = value,
+            0x2D => self.ts = value,
+            0x2E => self.tmw = value,
+            0x2F => self.tsw = value,
+            0x30 => self.cgwsel = value,
+            0x31 => self.cgadsub = value,
+            0x32 => self.write_coldata(value),
+            0x33 => self.setini = value,
+            _ => {}
+        }
+    }
+
+    fn set_position(&mut self, line: u64, dot: u64) {
+        self.catch_up(line, dot);
+        self.line = line;
+        self.dot = dot;
+    }
+
+    fn draw_frame(&mut self, buffer: &mut [u32]) {
+        buffer.copy_from_slice(&self.frame);
+    }
+}
@@ -0,0 +1,323 @@
+// snesemu - SNES emulator written in Rust
+// Copyright (C) 2017 Konrad Borowski
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Scanline renderer of background layers on the main screen.
+//!
+//! Every layer is drawn into a line of pixels, which are then picked front
+//! to back in the order given by the background mode. Pixels are 256 dots
+//! wide, or 512 dots wide in the high resolution modes 5 and 6.
+//!
+//! Mode 7 draws a single 1024×1024 background through an affine
+//! transformation, with an optional BG2 reusing its data where the top bit
+//! of every dot is its priority.
+
+use ppu::Ppu;
+
+/// Number of dots in a line of high resolution modes.
+pub const LINE_WIDTH: usize = 512;
+
+/// Bits of BGMODE.
+const BG3_PRIORITY: u8 = 0x08;
+
+/// Bits of tilemap entries.
+const PALETTE_SHIFT: u16 = 10;
+const PRIORITY: u16 = 0x2000;
+const FLIP_X: u16 = 0x4000;
+const FLIP_Y: u16 = 0x8000;
+
+/// Bit of SETINI, enabling BG2 in mode 7.
+const EXTBG: u8 = 0x40;
+
+/// Bits of M7SEL.
+const M7_FLIP_X: u8 = 0x01;
+const M7_FLIP_Y: u8 = 0x02;
+
+/// Bit of CGWSEL, taking colors of 256 color layers from their palette
+/// index rather than CGRAM.
+const DIRECT_COLOR: u8 = 0x01;
+
+/// Bits per pixel of BG1-BG4 in modes 0-6, with 0 for missing layers.
+const DEPTHS: [[u8; 4]; 7] = [[2, 2, 2, 2], [4, 4, 2, 0], [4, 4, 0, 0], [8, 4, 0, 0],
+                              [8, 2, 0, 0], [4, 2, 0, 0], [4, 0, 0, 0]];
+
+/// Layers from front to back, as a background and its priority bit.
+const MODE_0: &[(usize, bool)] = &[(0, true), (1, true), (0, false), (1, false), (2, true),
+                                   (3, true), (2, false), (3, false)];
+const MODE_1: &[(usize, bool)] = &[(0, true), (1, true), (0, false), (1, false), (2, true),
+                                   (2, false)];
+const MODE_1_BG3_PRIORITY: &[(usize, bool)] = &[(2, true), (0, true), (1, true), (0, false),
+                                                (1, false), (2, false)];
+const MODE_2_TO_5: &[(usize, bool)] = &[(0, true), (1, true), (0, false), (1, false)];
+const MODE_6: &[(usize, bool)] = &[(0, true), (0, false)];
+const MODE_7: &[(usize, bool)] = &[(1, true), (0, false), (1, false)];
+
+/// Pixel of a layer, as a BGR555 color and the priority bit of its tile.
+#[derive(Clone, Copy)]
+pub struct Pixel {
+    pub color: u16,
+    pub priority: bool,
+}
+
+/// Line of pixels of a layer, where `None` is transparent.
+pub type Layer = [Option<Pixel>; LINE_WIDTH];
+
+/// Converts a BGR555 color to 0RGB with 8 bits per component, scaled by
+/// the master brightness in the low 4 bits of INIDISP.
+pub fn rgb(color: u16, brightness: u8) -> u32 {
+    let brightness = (brightness & 0xF) as u32;
+    let component = |shift: u16| {
+        let value = (color >> shift & 0x1F) as u32;
+        let value = value << 3 | value >> 2;
+        // Brightness 0 is black, and 15 is full brightness
+        if brightness == 0 {
+            0
+        } else {
+            value * (brightness + 1) / 16
+        }
+    };
+    component(0) << 16 | component(5) << 8 | component(10)
+}
+
+/// Reads an entry of the tilemap of a background, by the column and row of
+/// a tile. Tilemaps consist of 1, 2 or 4 screens of 32×32 tiles.
+fn tilemap_entry(ppu: &Ppu, background: usize, column: u16, row: u16) -> u16 {
+    let bgsc = ppu.bgsc[background];
+    let mut address = ((bgsc as usize & 0xFC) << 8) + (row as usize & 31) * 32 +
+                      (column as usize & 31);
+    if column & 32 != 0 && bgsc & 1 != 0 {
+        address += 0x400;
+    }
+    if row & 32 != 0 && bgsc & 2 != 0 {
+        address += if bgsc & 1 != 0 { 0x800 } else { 0x400 };
+    }
+    ppu.vram[address & 0x7FFF]
+}
+
+/// Reads the color index of a dot of an 8×8 tile, stored as pairs of
+/// bitplanes interleaved by rows.
+fn tile_dot(ppu: &Ppu, address: usize, depth: u8, x: u16, y: u16) -> u8 {
+    let mut index = 0;
+    for pair in 0..depth as usize / 2 {
+        let word = ppu.vram[(address + pair * 8 + y as usize) & 0x7FFF];
+        let bit = 7 - x;
+        let low = (word >> bit & 1) as u8;
+        let high = (word >> (bit + 8) & 1) as u8;
+        index |= (low | high << 1) << (pair * 2);
+    }
+    index
+}
+
+/// Color of a dot of a 256 color tile in direct color mode. The index
+/// holds BBGGGRRR, and the palette number of the tile adds a lower bit to
+/// each component.
+fn direct_color(index: u8, palette: u16) -> u16 {
+    let index = index as u16;
+    let red = (index & 7) << 2 | (palette & 1) << 1;
+    let green = (index >> 3 & 7) << 2 | (palette & 2);
+    let blue = (index >> 6 & 3) << 3 | (palette & 4);
+    red | green << 5 | blue << 10
+}
+
+/// Applies offset-per-tile of modes 2, 4 and 6, where the first rows of
+/// the BG3 tilemap replace scroll values of BG1 and BG2 for every column
+/// of tiles other than the leftmost one.
+fn offset_per_tile(ppu: &Ppu,
+                   background: usize,
+                   mode: u8,
+                   x: u16,
+                   scroll: (u16, u16))
+                   -> (u16, u16) {
+    let (mut hofs, mut vofs) = scroll;
+    let column = (x + (hofs & 7)) / 8;
+    if column == 0 {
+        return scroll;
+    }
+    let valid = PRIORITY << background;
+    let bg3_column = ((column - 1) * 8 + (ppu.hofs[2] & !7)) / 8;
+    let bg3_row = ppu.vofs[2] / 8;
+    let horizontal = tilemap_entry(ppu, 2, bg3_column, bg3_row);
+    if mode == 4 {
+        // A single row, with the top bit choosing the direction
+        if horizontal & valid != 0 {
+            if horizontal & 0x8000 == 0 {
+                hofs = horizontal & 0x3F8 | hofs & 7;
+            } else {
+                vofs = horizontal & 0x3FF;
+            }
+        }
+    } else {
+        let vertical = tilemap_entry(ppu, 2, bg3_column, bg3_row + 1);
+        if horizontal & valid != 0 {
+            hofs = horizontal & 0x3F8 | hofs & 7;
+        }
+        if vertical & valid != 0 {
+            vofs = vertical & 0x3FF;
+        }
+    }
+    (hofs, vofs)
+}
+
+/// Draws a scanline of a background in modes 0-6.
+fn background(ppu: &Ppu, background: usize, line: u16, output: &mut Layer) {
+    let mode = ppu.bgmode & 7;
+    let depth = DEPTHS[mode as usize][background];
+    let hires = mode == 5 || mode == 6;
+    let width = if hires { 512 } else { 256 };
+    let large = ppu.bgmode & 0x10 << background != 0;
+    let tile_width = if large || hires { 16 } else { 8 };
+    let tile_height = if large { 16 } else { 8 };
+    let nba = ppu.bgnba[background / 2] >> (background % 2 * 4) & 0xF;
+    let tiles = (nba as usize) << 12;
+    let words_per_tile = depth as usize * 4;
+    // Mode 0 gives every background its own 32 colors
+    let palette_base = if mode == 0 { background as u16 * 32 } else { 0 };
+    for (x, output) in output[..width].iter_mut().enumerate() {
+        let x = x as u16;
+        let mut scroll = (ppu.hofs[background], ppu.vofs[background]);
+        if matches!(mode, 2 | 4 | 6) {
+            scroll = offset_per_tile(ppu, background, mode, if hires { x / 2 } else { x }, scroll);
+        }
+        let (hofs, vofs) = scroll;
+        // High resolution modes scroll by pairs of dots
+        let x = if hires { x + hofs * 2 } else { x + hofs } & 0x3FF;
+        let y = (line + vofs) & 0x3FF;
+        let entry = tilemap_entry(ppu, background, x / tile_width, y / tile_height);
+        let (mut x, mut y) = (x % tile_width, y % tile_height);
+        if entry & FLIP_X != 0 {
+            x = tile_width - 1 - x;
+        }
+        if entry & FLIP_Y != 0 {
+            y = tile_height - 1 - y;
+        }
+        let tile = ((entry & 0x3FF) + x / 8 + y / 8 * 16) & 0x3FF;
+        let index = tile_dot(ppu, tiles + tile as usize * words_per_tile, depth, x % 8, y % 8);
+        if index == 0 {
+            *output = None;
+            continue;
+        }
+        let palette = entry >> PALETTE_SHIFT & 7;
+        let color = match depth {
+            2 => ppu.cgram[(palette_base + palette * 4 + index as u16) as usize],
+            4 => ppu.cgram[(palette * 16 + index as u16) as usize],
+            _ if ppu.cgwsel & DIRECT_COLOR != 0 => direct_color(index, palette),
+            _ => ppu.cgram[index as usize],
+        };
+        *output = Some(Pixel {
+            color,
+            priority: entry & PRIORITY != 0,
+        });
+    }
+}
+
+/// Clips a 13-bit signed difference of a scroll value and the center of
+/// rotation to 10 bits, keeping its sign.
+fn clip(value: i32) -> i32 {
+    if value & 0x2000 != 0 {
+        value | !0x3FF
+    } else {
+        value & 0x3FF
+    }
+}
+
+/// Draws a scanline of the mode 7 background as BG1, along with BG2 when
+/// EXTBG is enabled. Outside of the tilemap, M7SEL either repeats it,
+/// leaves dots transparent or fills them with tile 0.
+fn mode_7(ppu: &Ppu, line: u16, bg1: &mut Layer, bg2: &mut Layer) {
+    let (a, b, c, d) = (ppu.m7a as i32, ppu.m7b as i32, ppu.m7c as i32, ppu.m7d as i32);
+    let (cx, cy) = (ppu.m7x as i32, ppu.m7y as i32);
+    let hofs = clip(ppu.m7hofs as i32 - cx);
+    let vofs = clip(ppu.m7vofs as i32 - cy);
+    let y = if ppu.m7sel & M7_FLIP_Y != 0 { 255 - line as i32 } else { line as i32 };
+    // Position of the left edge of the line in the tilemap, in 8.8 fixed
+    // point, with bits lost to the precision of the hardware
+    let origin_x = ((a * hofs) & !63) + ((b * vofs) & !63) + ((b * y) & !63) + (cx << 8);
+    let origin_y = ((c * hofs) & !63) + ((d * vofs) & !63) + ((d * y) & !63) + (cy << 8);
+    let enable_bg1 = ppu.tm & 1 != 0;
+    let enable_bg2 = ppu.tm & 2 != 0 && ppu.setini & EXTBG != 0;
+    let color = |index: u8| if ppu.cgwsel & DIRECT_COLOR != 0 {
+        direct_color(index, 0)
+    } else {
+        ppu.cgram[index as usize]
+    };
+    for screen_x in 0..256 {
+        let x = if ppu.m7sel & M7_FLIP_X != 0 { 255 - screen_x } else { screen_x };
+        let px = (origin_x + a * x as i32) >> 8;
+        let py = (origin_y + c * x as i32) >> 8;
+        let outside = px & !0x3FF != 0 || py & !0x3FF != 0;
+        let tile = match ppu.m7sel >> 6 {
+            2 if outside => continue,
+            3 if outside => 0,
+            // The tilemap repeats
+            _ => ppu.vram[((py & 0x3FF) >> 3 << 7 | (px & 0x3FF) >> 3) as usize] & 0xFF,
+        };
+        let index = (ppu.vram[(tile << 6 | (py as u16 & 7) << 3 | px as u16 & 7) as usize] >>
+                     8) as u8;
+        if enable_bg1 && index != 0 {
+            bg1[screen_x] = Some(Pixel {
+                color: color(index),
+                priority: false,
+            });
+        }
+        if enable_bg2 && index & 0x7F != 0 {
+            bg2[screen_x] = Some(Pixel {
+                color: ppu.cgram[(index & 0x7F) as usize],
+                priority: index & 0x80 != 0,
+            });
+        }
+    }
+}
+
+/// Draws a scanline of the main screen as 0RGB colors, 512 dots wide.
+pub fn line(ppu: &Ppu, line: u16, output: &mut [u32]) {
+    let brightness = ppu.inidisp & 0xF;
+    if ppu.inidisp & 0x80 != 0 {
+        for dot in output.iter_mut() {
+            *dot = 0;
+        }
+        return;
+    }
+    let mode = ppu.bgmode & 7;
+    let order = match mode {
+        0 => MODE_0,
+        1 if ppu.bgmode & BG3_PRIORITY != 0 => MODE_1_BG3_PRIORITY,
+        1 => MODE_1,
+        2...5 => MODE_2_TO_5,
+        6 => MODE_6,
+        _ => MODE_7,
+    };
+    let mut layers = [[None; LINE_WIDTH]; 4];
+    if mode == 7 {
+        let (bg1, bg2) = layers.split_at_mut(1);
+        mode_7(ppu, line, &mut bg1[0], &mut bg2[0]);
+    } else {
+        for (background, layer) in layers.iter_mut().enumerate() {
+            if ppu.tm & 1 << background != 0 && order.iter().any(|&(bg, _)| bg == background) {
+                self::background(ppu, background, line, layer);
+            }
+        }
+    }
+    let hires = mode == 5 || mode == 6;
+    for (x, dot) in output.iter_mut().enumerate() {
+        let x = if hires { x } else { x / 2 };
+        let color = order.iter()
+            .filter_map(|&(background, priority)| {
+                layers[background][x].filter(|pixel| pixel.priority == priority)
+            })
+            .next()
+            .map_or(ppu.cgram[0], |pixel| pixel.color);
+        *dot = rgb(color, brightness);
+    }
+}
@@ -0,0 +1,101 @@
+extern crate snesemu_cpu;
+
+use snesemu_cpu::cpu::{CPU, State};
+use snesemu_cpu::mapper::LoROM;
+
+fn create_rom(input: &[u8]) -> [u8; 0x10000] {
+    let mut output = [0; 0x10000];
+    output[..input.len()].copy_from_slice(input);
+    output[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    output
+}
+
+fn assert_same(a: &CPU<LoROM>, b: &CPU<LoROM>) {
+    let (x, y) = (&a.registers, &b.registers);
+    assert_eq!((x.a, x.x, x.y, x.dp, x.sp, x.db, x.pb, x.pc),
+               (y.a, y.x, y.y, y.dp, y.sp, y.db, y.pb, y.pc));
+    assert_eq!((x.status(), x.emulation), (y.status(), y.emulation));
+    assert_eq!((a.cycles, a.state), (b.cycles, b.state));
+    assert!(a.bus.wram[..] == b.bus.wram[..]);
+}
+
+/// Runs a program with `run_until` in small slices of time, checking that
+/// it does the same thing as stepping through it.
+fn compare(rom: &[u8], setup: fn(&mut CPU<LoROM>), slice: u64, slices: u32) {
+    let mut stepped = CPU::new(LoROM::new(rom));
+    let mut cached = CPU::new(LoROM::new(rom));
+    setup(&mut stepped);
+    setup(&mut cached);
+    let mut end = cached.cycles;
+    for _ in 0..slices {
+        end += slice;
+        while stepped.cycles < end && stepped.step().unwrap() == State::Running {}
+        cached.run_until(end).unwrap();
+        assert_same(&stepped, &cached);
+    }
+}
+
+#[test]
+fn same_as_step() {
+    let bytes = create_rom(&[0x18, // CLC
+                             0xFB, // XCE
+                             0xC2, 0x30, // REP #$30
+                             0xA2, 0x00, 0x00, // LDX #$0000
+                             0x7D, 0x00, 0x10, // ADC $1000,X
+                             0x9D, 0x00, 0x10, // STA $1000,X
+                             0xE8, // INX
+                             0xE0, 0x00, 0x01, // CPX #$0100
+                             0xD0, 0xF4, // BNE $8007
+                             0xE2, 0x30, // SEP #$30
+                             0xC2, 0x30, // REP #$30
+                             0x80, 0xE7]); // BRA $8004
+    for &slice in &[1, 7, 100, 1364] {
+        compare(&bytes, |_| {}, slice, 500);
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    // JML $7E1000
+    let bytes = create_rom(&[0x5C, 0x00, 0x10, 0x7E]);
+    // LDA #$00; INC A; STA $1001; BRA $1000, which increments its own
+    // immediate operand through a mirror of the RAM.
+    fn setup(cpu: &mut CPU<LoROM>) {
+        cpu.bus.wram[0x1000..0x1008]
+            .copy_from_slice(&[0xA9, 0x00, 0x1A, 0x8D, 0x01, 0x10, 0x80, 0xF8]);
+    }
+    compare(&bytes, setup, 1000, 50);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    setup(&mut cpu);
+    cpu.run_until(100_000).unwrap();
+    assert!(cpu.registers.a > 1);
+}
+
+#[test]
+fn overwriting_next_instruction() {
+    // JML $7E1000
+    let bytes = create_rom(&[0x5C, 0x00, 0x10, 0x7E]);
+    // LDA #$EA; STA $1005; STP; INX; STP, where STP gets replaced by NOP
+    // after its block was decoded.
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.bus.wram[0x1000..0x1008]
+        .copy_from_slice(&[0xA9, 0xEA, 0x8D, 0x05, 0x10, 0xDB, 0xE8, 0xDB]);
+    assert_eq!(State::Stopped, cpu.run_until(100_000).unwrap());
+    assert_eq!(0x1008, cpu.registers.pc);
+    assert_eq!(1, cpu.registers.x);
+}
+
+#[test]
+fn overwriting_through_wram_port() {
+    // JML $7E1000
+    let bytes = create_rom(&[0x5C, 0x00, 0x10, 0x7E]);
+    // LDA #$EA; STA $2180; STP; INX; STP, with WMADD pointing at the
+    // first STP.
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.bus.wram[0x1000..0x1008]
+        .copy_from_slice(&[0xA9, 0xEA, 0x8D, 0x80, 0x21, 0xDB, 0xE8, 0xDB]);
+    cpu.bus.wram_address = 0x1005;
+    assert_eq!(State::Stopped, cpu.run_until(100_000).unwrap());
+    assert_eq!(0x1008, cpu.registers.pc);
+    assert_eq!(1, cpu.registers.x);
+}
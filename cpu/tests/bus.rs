@@ -0,0 +1,168 @@
+extern crate snesemu_cpu;
+
+mod common;
+
+use snesemu_cpu::bus::Bus;
+use snesemu_cpu::Emulator;
+use snesemu_cpu::mapper::LoROM;
+
+use common::Recorder;
+
+fn create_rom() -> Vec<u8> {
+    (0..0x10000).map(|i| (i >> 8) as u8).collect()
+}
+
+#[test]
+fn wram_mirrors() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    bus.write(0x80, 0x1234, 0x12).unwrap();
+    bus.write(0x3F, 0x0042, 0x34).unwrap();
+    bus.write(0x7F, 0x1234, 0x56).unwrap();
+    assert_eq!(0x12, bus.read(0x7E, 0x1234));
+    assert_eq!(0x34, bus.read(0xBF, 0x0042));
+    assert_eq!(0x56, bus.wram[0x11234]);
+    // Only the first 8 KiB are mirrored
+    assert_eq!(0x55, bus.read(0x7E, 0x2042));
+}
+
+#[test]
+fn save_ram() {
+    let mut rom = create_rom();
+    // 2 KiB of save RAM
+    rom[0x7FD8] = 0x01;
+    let mut bus = Bus::new(LoROM::new(&rom));
+    assert_eq!(0x800, bus.mapper.sram.len());
+    bus.write(0x70, 0x0123, 0x12).unwrap();
+    bus.write(0xFD, 0x7FFF, 0x34).unwrap();
+    assert_eq!(0x12, bus.read(0x71, 0x0923));
+    assert_eq!(0x34, bus.mapper.sram[0x7FF]);
+    // The upper halves still belong to ROM
+    assert!(bus.write(0x70, 0x8000, 0x56).is_err());
+}
+
+#[test]
+fn b_bus() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    let (ppu, apu) = (Recorder::default(), Recorder::default());
+    bus.ppu = Box::new(ppu.clone());
+    bus.apu = Box::new(apu.clone());
+    bus.write(0x00, 0x2118, 0xAB).unwrap();
+    assert_eq!(0x3F, bus.read(0x80, 0x213F));
+    bus.write(0xBF, 0x2141, 0xCD).unwrap();
+    assert_eq!(0x7F, bus.read(0x00, 0x217F));
+    bus.write_b(0x40, 0xEF);
+    assert_eq!(vec![(0x18, Some(0xAB)), (0x3F, None)], *ppu.accesses.lock().unwrap());
+    assert_eq!(vec![(0x41, Some(0xCD)), (0x7F, None), (0x40, Some(0xEF))],
+               *apu.accesses.lock().unwrap());
+    // Peeking doesn't touch registers
+    assert_eq!(0x7F, bus.peek(0x00, 0x2140));
+    assert_eq!(3, apu.accesses.lock().unwrap().len());
+}
+
+#[test]
+fn emulator_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Emulator<LoROM<'static>>>();
+}
+
+#[test]
+fn cpu_registers() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    assert_eq!(8, bus.access_cycles(0x80, 0x8000));
+    bus.write(0x80, 0x420D, 0x01).unwrap();
+    assert!(bus.io.fast_rom);
+    assert_eq!(6, bus.access_cycles(0x80, 0x8000));
+    bus.write(0x00, 0x4312, 0x34).unwrap();
+    bus.write(0x80, 0x4313, 0x12).unwrap();
+    bus.write(0x00, 0x437F, 0x56).unwrap();
+    assert_eq!(0x1234, bus.dma.channels[1].a_address);
+    assert_eq!(0x12, bus.read(0x00, 0x4313));
+    assert_eq!(0x56, bus.read(0x00, 0x437B));
+}
+
+#[test]
+fn cartridge() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    assert_eq!(0x01, bus.read(0x00, 0x8100));
+    assert_eq!(0x81, bus.read(0x81, 0x8100));
+    assert_eq!(0xFF, bus.read(0x01, 0xFF00));
+    assert!(bus.write(0x80, 0x8000, 0x00).is_err());
+    // Banks $40-$7D have no registers
+    assert!(bus.write(0x40, 0x2100, 0x00).is_err());
+}
+
+#[test]
+fn open_bus() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    bus.write(0x7E, 0x0000, 0x12).unwrap();
+    // Outside of the ROM
+    assert_eq!(0x12, bus.read(0x10, 0x8000));
+    // Expansion area and write-only registers
+    assert_eq!(0x12, bus.read(0x00, 0x6000));
+    assert_eq!(0x12, bus.read(0x00, 0x4200));
+    assert_eq!(0x12, bus.read(0x00, 0x21FF));
+    assert_eq!(0x12, bus.read(0x00, 0x430C));
+    assert_eq!(0x01, bus.read(0x00, 0x8180));
+    assert_eq!(0x01, bus.peek(0x00, 0x2000));
+}
+
+#[test]
+fn partial_open_bus() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    bus.mdr = 0xFF;
+    assert_eq!(0xFC, bus.read(0x00, 0x4016));
+    assert_eq!(0xFC, bus.read(0x00, 0x4017));
+    bus.mdr = 0xFF;
+    assert_eq!(0x72, bus.read(0x00, 0x4210));
+    assert_eq!(0x72, bus.read(0x00, 0x4211));
+    // Horizontal blanking lasts until the first dot of a scanline
+    assert_eq!(0x72, bus.read(0x00, 0x4212));
+}
+
+#[test]
+fn ppu_open_bus() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    bus.mdr = 0xFF;
+    // STAT77 and STAT78, with versions and the open bus bit of each chip
+    assert_eq!(0x01, bus.read(0x00, 0x213E));
+    assert_eq!(0x03, bus.read(0x00, 0x213F));
+    // Write-only registers inside of PPU1 return its latch, others return
+    // open bus of the CPU
+    assert_eq!(0x01, bus.read(0x00, 0x2104));
+    assert_eq!(0x01, bus.read(0x00, 0x2100));
+    bus.mdr = 0xFF;
+    assert_eq!(0xFF, bus.read(0x00, 0x2137));
+    assert_eq!(0x01, bus.read(0x00, 0x2118));
+}
+
+#[test]
+fn wram_port() {
+    let rom = create_rom();
+    let mut bus = Bus::new(LoROM::new(&rom));
+    bus.write(0x00, 0x2181, 0xFE).unwrap();
+    bus.write(0x00, 0x2182, 0xFF).unwrap();
+    bus.write(0x00, 0x2183, 0xFF).unwrap();
+    assert_eq!(0x1FFFE, bus.wram_address);
+    bus.write(0x00, 0x2180, 0x12).unwrap();
+    bus.write(0x80, 0x2180, 0x34).unwrap();
+    // The address wraps around to the start of work RAM
+    bus.write(0x00, 0x2180, 0x56).unwrap();
+    assert_eq!(0x0001, bus.wram_address);
+    assert_eq!([0x12, 0x34], bus.wram[0x1FFFE..]);
+    assert_eq!(0x56, bus.read(0x7E, 0x0000));
+    bus.write(0x00, 0x2183, 0x01).unwrap();
+    bus.write(0x00, 0x2181, 0xFF).unwrap();
+    bus.write(0x00, 0x2182, 0xFF).unwrap();
+    assert_eq!(0x34, bus.read(0x00, 0x2180));
+    assert_eq!(0x56, bus.read(0x00, 0x2180));
+    assert_eq!(0x00001, bus.wram_address);
+    // The address registers are write-only
+    assert_eq!(0x56, bus.read(0x00, 0x2181));
+}
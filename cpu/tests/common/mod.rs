@@ -0,0 +1,25 @@
+//! Helpers shared between integration tests.
+
+use std::sync::{Arc, Mutex};
+
+use snesemu_cpu::bus::Device;
+
+/// Addresses of accesses, along with written values.
+pub type Accesses = Vec<(u8, Option<u8>)>;
+
+/// Device remembering accesses, answering reads with their address.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    pub accesses: Arc<Mutex<Accesses>>,
+}
+
+impl Device for Recorder {
+    fn read(&mut self, address: u8, _open_bus: u8) -> u8 {
+        self.accesses.lock().unwrap().push((address, None));
+        address
+    }
+
+    fn write(&mut self, address: u8, value: u8) {
+        self.accesses.lock().unwrap().push((address, Some(value)));
+    }
+}
@@ -0,0 +1,225 @@
+extern crate snesemu_cpu;
+
+mod common;
+
+use snesemu_cpu::cpu::{CPU, State};
+use snesemu_cpu::io::{CYCLES_PER_FRAME, CYCLES_PER_LINE, VBLANK_LINE};
+use snesemu_cpu::mapper::LoROM;
+
+use common::{Accesses, Recorder};
+
+/// ROM which sets up a channel with its registers, then starts DMA on it.
+fn create_rom(channel: u8, registers: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    let mut code = vec![0xE2, 0x20]; // SEP #$20
+    for (i, &value) in registers.iter().enumerate() {
+        // LDA #value; STA $43xx
+        code.extend_from_slice(&[0xA9, value, 0x8D, channel << 4 | i as u8, 0x43]);
+    }
+    // LDA #channel; STA $420B; STP
+    code.extend_from_slice(&[0xA9, 1 << channel, 0x8D, 0x0B, 0x42, 0xDB]);
+    rom[..code.len()].copy_from_slice(&code);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    rom
+}
+
+/// Sets up HDMA on a channel, with its table at $7E1000.
+fn setup_hdma(cpu: &mut CPU<LoROM>, index: usize, control: u8, table: &[u8]) {
+    cpu.bus.wram[0x1000..0x1000 + table.len()].copy_from_slice(table);
+    let channel = &mut cpu.bus.dma.channels[index];
+    channel.control = control;
+    channel.b_address = 0x18;
+    channel.a_address = 0x1000;
+    channel.a_bank = 0x7E;
+    channel.indirect_bank = 0x7E;
+    cpu.bus.dma.hdmaen |= 1 << index;
+}
+
+/// Values written by a recorder.
+fn written(recorder: &Recorder) -> Vec<u8> {
+    recorder.accesses.lock().unwrap().iter().filter_map(|&(_, value)| value).collect()
+}
+
+fn run(cpu: &mut CPU<LoROM>) {
+    while cpu.step().unwrap() == State::Running {}
+}
+
+#[test]
+fn transfer_patterns() {
+    let expected: [&[u8]; 8] = [&[0, 0, 0, 0],
+                                &[0, 1, 0, 1],
+                                &[0, 0, 0, 0],
+                                &[0, 0, 1, 1],
+                                &[0, 1, 2, 3],
+                                &[0, 1, 0, 1],
+                                &[0, 0, 0, 0],
+                                &[0, 0, 1, 1]];
+    for (pattern, expected) in expected.iter().enumerate() {
+        // From $7E1000 to $2118, 4 bytes
+        let rom = create_rom(3, &[pattern as u8, 0x18, 0x00, 0x10, 0x7E, 0x04, 0x00]);
+        let mut cpu = CPU::new(LoROM::new(&rom));
+        let ppu = Recorder::default();
+        cpu.bus.ppu = Box::new(ppu.clone());
+        cpu.bus.wram[0x1000..0x1004].copy_from_slice(&[0xA0, 0xA1, 0xA2, 0xA3]);
+        run(&mut cpu);
+        let accesses: Accesses = expected.iter()
+            .zip(0xA0..)
+            .map(|(&offset, value)| (0x18 + offset, Some(value)))
+            .collect();
+        assert_eq!(accesses, *ppu.accesses.lock().unwrap());
+        let channel = cpu.bus.dma.channels[3];
+        assert_eq!((0x1004, 0x7E, 0, 0x18),
+                   (channel.a_address, channel.a_bank, channel.count, channel.b_address));
+    }
+}
+
+#[test]
+fn b_to_a_decrement() {
+    // From $2140-$2141 to $7E1003 downwards, 4 bytes
+    let rom = create_rom(0, &[0x91, 0x40, 0x03, 0x10, 0x7E, 0x04, 0x00]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    cpu.bus.apu = Box::new(Recorder::default());
+    run(&mut cpu);
+    assert_eq!([0x41, 0x40, 0x41, 0x40], cpu.bus.wram[0x1000..0x1004]);
+    assert_eq!(0x0FFF, cpu.bus.dma.channels[0].a_address);
+}
+
+#[test]
+fn fill_work_ram() {
+    // From fixed $00FFFF, which is 0x00, to WMDATA, 0x10000 bytes
+    let rom = create_rom(7, &[0x08, 0x80, 0xFF, 0xFF, 0x00, 0x00, 0x00]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    cpu.bus.wram_address = 0x10000;
+    run(&mut cpu);
+    assert!(cpu.bus.wram[0x10000..].iter().all(|&byte| byte == 0));
+    assert!(cpu.bus.wram[..0x10000].iter().all(|&byte| byte == 0x55));
+    assert_eq!(0xFFFF, cpu.bus.dma.channels[7].a_address);
+    assert_eq!(0x00000, cpu.bus.wram_address);
+}
+
+#[test]
+fn invalid_addresses() {
+    let ppu = Recorder::default();
+    // From work RAM to WMDATA, which does nothing
+    let rom = create_rom(0, &[0x00, 0x80, 0x00, 0x10, 0x7E, 0x02, 0x00]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    run(&mut cpu);
+    assert_eq!(0, cpu.bus.wram_address);
+    // From registers on the A-bus, which read as open bus
+    let rom = create_rom(0, &[0x00, 0x18, 0x00, 0x21, 0x00, 0x01, 0x00]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    cpu.bus.ppu = Box::new(ppu.clone());
+    run(&mut cpu);
+    assert_eq!(vec![(0x18, Some(0x01))], *ppu.accesses.lock().unwrap());
+}
+
+#[test]
+fn timing() {
+    for &count in &[1, 2, 100] {
+        let rom = create_rom(0, &[0x00, 0x18, 0x00, 0x10, 0x7E, count, 0x00]);
+        let mut cpu = CPU::new(LoROM::new(&rom));
+        let mut before = 0;
+        while cpu.registers.pc != 0x8027 {
+            cpu.step().unwrap();
+            before = cpu.cycles;
+        }
+        // STA $420B
+        cpu.step().unwrap();
+        let instruction = 8 * 4;
+        let alignment = (8 - (before + instruction) % 8) % 8;
+        assert_eq!(instruction + alignment + 8 + 8 + 8 * count as u64,
+                   cpu.cycles - before);
+    }
+}
+
+#[test]
+fn hdma_line_counts() {
+    // BRA $8000
+    let mut rom = create_rom(0, &[]);
+    rom[..2].copy_from_slice(&[0x80, 0xFE]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    let ppu = Recorder::default();
+    cpu.bus.ppu = Box::new(ppu.clone());
+    // 2 lines with a single transfer, then 1 and 2 lines repeating one
+    setup_hdma(&mut cpu, 2, 0x00, &[0x02, 0xAA, 0x81, 0xBB, 0x82, 0xCC, 0xDD, 0x00]);
+    cpu.run_until(2 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA], written(&ppu));
+    cpu.run_until(3 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA, 0xBB], written(&ppu));
+    cpu.run_until(100 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA, 0xBB, 0xCC, 0xDD], written(&ppu));
+    assert_eq!(0x1008, cpu.bus.dma.channels[2].table_address);
+    // The table starts over in the next frame
+    cpu.run_until(263 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA, 0xBB, 0xCC, 0xDD, 0xAA], written(&ppu));
+}
+
+#[test]
+fn hdma_while_stopped() {
+    // LDA #$80; STA $4200; WAI; BRA $8005
+    let mut rom = create_rom(0, &[]);
+    rom[..8].copy_from_slice(&[0xA9, 0x80, 0x8D, 0x00, 0x42, 0xCB, 0x80, 0xFD]);
+    // NMI handler at $8100 increments X
+    rom[0x100..0x102].copy_from_slice(&[0xE8, 0x40]);
+    rom[0x7FFA..0x7FFC].copy_from_slice(&[0x00, 0x81]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    let ppu = Recorder::default();
+    cpu.bus.ppu = Box::new(ppu.clone());
+    setup_hdma(&mut cpu, 0, 0x00, &[0x01, 0xAA, 0x00]);
+    cpu.state = State::Stopped;
+    // HDMA runs once per frame while stopped
+    cpu.run_until(CYCLES_PER_FRAME + 2 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA, 0xAA], written(&ppu));
+    // Nothing missed gets replayed after a reset, and NMI comes on time
+    cpu.reset();
+    cpu.run_until(CYCLES_PER_FRAME + VBLANK_LINE * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA, 0xAA], written(&ppu));
+    assert_eq!(0, cpu.registers.x);
+    cpu.run_until(CYCLES_PER_FRAME + (VBLANK_LINE + 1) * CYCLES_PER_LINE).unwrap();
+    assert_eq!(1, cpu.registers.x);
+    cpu.run_until(2 * CYCLES_PER_FRAME + 2 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![0xAA, 0xAA, 0xAA], written(&ppu));
+}
+
+#[test]
+fn hdma_indirect() {
+    let mut rom = create_rom(0, &[]);
+    rom[..2].copy_from_slice(&[0x80, 0xFE]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    let ppu = Recorder::default();
+    cpu.bus.ppu = Box::new(ppu.clone());
+    // 2 lines of two registers each, from $7E2000
+    setup_hdma(&mut cpu, 0, 0x41, &[0x82, 0x00, 0x20, 0x00]);
+    cpu.bus.wram[0x2000..0x2004].copy_from_slice(&[0x11, 0x22, 0x33, 0x44]);
+    cpu.run_until(10 * CYCLES_PER_LINE).unwrap();
+    assert_eq!(vec![(0x18, Some(0x11)), (0x19, Some(0x22)), (0x18, Some(0x33)),
+                    (0x19, Some(0x44))],
+               *ppu.accesses.lock().unwrap());
+    assert_eq!(0x2004, cpu.bus.dma.channels[0].count);
+    assert_eq!(0x1004, cpu.bus.dma.channels[0].table_address);
+}
+
+#[test]
+fn hdma_during_dma() {
+    for &index in &[0, 1] {
+        // DMA of 0x1000 bytes from $7E2000 on channel 0, with HDMA of a
+        // single line on channel `index`
+        let rom = create_rom(0, &[0x00, 0x18, 0x00, 0x20, 0x7E, 0x00, 0x10]);
+        let mut cpu = CPU::new(LoROM::new(&rom));
+        let ppu = Recorder::default();
+        cpu.bus.ppu = Box::new(ppu.clone());
+        setup_hdma(&mut cpu, index, 0x00, &[0x01, 0xAB, 0x00]);
+        run(&mut cpu);
+        let written = written(&ppu);
+        if index == 0 {
+            // HDMA on the same channel cancels DMA
+            assert_eq!(Some(&0xAB), written.last());
+            assert!(cpu.bus.dma.channels[0].count != 0);
+        } else {
+            // DMA resumes after HDMA
+            assert_eq!(0x1001, written.len());
+            assert_eq!(1, written.iter().filter(|&&value| value == 0xAB).count());
+            assert_eq!(0, cpu.bus.dma.channels[0].count);
+        }
+    }
+}
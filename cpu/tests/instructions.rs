@@ -1,6 +1,8 @@
 extern crate snesemu_cpu;
 
-use snesemu_cpu::cpu::CPU;
+use snesemu_cpu::cpu::{CPU, Interrupt, State, Flags, FLAG_CARRY, FLAG_ZERO, FLAG_NO_IRQ,
+                       FLAG_DECIMAL, FLAG_XY16, FLAG_A16, FLAG_OVERFLOW, FLAG_NEGATIVE};
+use snesemu_cpu::error::{Error, ErrorPolicy};
 use snesemu_cpu::mapper::LoROM;
 
 fn create_rom(input: &[u8]) -> [u8; 0x10000] {
@@ -17,7 +19,7 @@ fn create_rom(input: &[u8]) -> [u8; 0x10000] {
 fn lda_immediate() {
     let bytes = create_rom(&[0xA9, 0x20]);
     let mut cpu = CPU::new(LoROM::new(&bytes));
-    cpu.step();
+    cpu.step().unwrap();
     assert_eq!(0x20, cpu.registers.a);
 }
 
@@ -25,11 +27,11 @@ fn lda_immediate() {
 fn lda_absolute() {
     let bytes = create_rom(&[0xAD, 0x00, 0x00, 0xAD, 0x01, 0x00]);
     let mut cpu = CPU::new(LoROM::new(&bytes));
-    cpu.ram[0] = 35;
-    cpu.ram[1] = 77;
-    cpu.step();
+    cpu.bus.wram[0] = 35;
+    cpu.bus.wram[1] = 77;
+    cpu.step().unwrap();
     assert_eq!(35, cpu.registers.a);
-    cpu.step();
+    cpu.step().unwrap();
     assert_eq!(77, cpu.registers.a);
 }
 
@@ -39,6 +41,891 @@ fn bra() {
     let mut cpu = CPU::new(LoROM::new(&bytes));
     for _ in 0..20 {
         assert_eq!(0x8000, cpu.registers.pc);
-        cpu.step();
+        cpu.step().unwrap();
     }
 }
+
+#[test]
+fn lda_sixteen_bits() {
+    let bytes = create_rom(&[0xA9, 0x34, 0x12, 0xA5, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags.insert(FLAG_A16);
+    cpu.bus.wram[0x0110] = 0xCD;
+    cpu.bus.wram[0x0111] = 0xAB;
+    cpu.step().unwrap();
+    assert_eq!(0x1234, cpu.registers.a);
+    cpu.registers.dp = 0x0100;
+    cpu.step().unwrap();
+    assert_eq!(0xABCD, cpu.registers.a);
+}
+
+#[test]
+fn lda_indirect_addressing() {
+    // LDA ($10),Y; LDA [$20],Y; LDA ($30,X)
+    let bytes = create_rom(&[0xB1, 0x10, 0xB7, 0x20, 0xA1, 0x2E]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.x = 0x02;
+    cpu.registers.y = 0x03;
+    cpu.registers.db = 0x7E;
+    cpu.bus.wram[0x10..0x12].copy_from_slice(&[0x00, 0x02]);
+    cpu.bus.wram[0x20..0x23].copy_from_slice(&[0x00, 0x03, 0x7F]);
+    cpu.bus.wram[0x30..0x32].copy_from_slice(&[0x00, 0x04]);
+    cpu.bus.wram[0x0203] = 11;
+    cpu.bus.wram[0x1_0303] = 22;
+    cpu.bus.wram[0x0400] = 33;
+    cpu.step().unwrap();
+    assert_eq!(11, cpu.registers.a);
+    cpu.step().unwrap();
+    assert_eq!(22, cpu.registers.a);
+    cpu.step().unwrap();
+    assert_eq!(33, cpu.registers.a);
+}
+
+#[test]
+fn store_instructions() {
+    // STA $10; STX $11; STY $1000; STZ $12
+    let bytes = create_rom(&[0x85, 0x10, 0x86, 0x11, 0x8C, 0x00, 0x10, 0x64, 0x12]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x12;
+    cpu.registers.x = 0x34;
+    cpu.registers.y = 0x56;
+    for _ in 0..4 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(&[0x12, 0x34, 0x00], &cpu.bus.wram[0x10..0x13]);
+    assert_eq!(0x56, cpu.bus.wram[0x1000]);
+}
+
+#[test]
+fn ldx_uses_index_width() {
+    // LDX #$12; LDY #$5634
+    let bytes = create_rom(&[0xA2, 0x12, 0xA0, 0x34, 0x56]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags.insert(FLAG_A16);
+    cpu.step().unwrap();
+    assert_eq!(0x12, cpu.registers.x);
+    cpu.registers.flags.insert(FLAG_XY16);
+    cpu.step().unwrap();
+    assert_eq!(0x5634, cpu.registers.y);
+}
+
+#[test]
+fn transfers() {
+    // TAX; TXY; TCD; TCS; XBA; TYA
+    let bytes = create_rom(&[0xAA, 0x9B, 0x5B, 0x1B, 0xEB, 0x98]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.a = 0x1234;
+    cpu.step().unwrap();
+    assert_eq!(0x34, cpu.registers.x);
+    cpu.step().unwrap();
+    assert_eq!(0x34, cpu.registers.y);
+    cpu.step().unwrap();
+    assert_eq!(0x1234, cpu.registers.dp);
+    cpu.step().unwrap();
+    assert_eq!(0x1234, cpu.registers.sp);
+    cpu.step().unwrap();
+    assert_eq!(0x3412, cpu.registers.a);
+    cpu.step().unwrap();
+    assert_eq!(0x3434, cpu.registers.a);
+}
+
+#[test]
+fn index_increments_wrap_at_index_width() {
+    // INX; DEY; INX
+    let bytes = create_rom(&[0xE8, 0x88, 0xE8]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.x = 0xFF;
+    cpu.step().unwrap();
+    assert_eq!(0x00, cpu.registers.x);
+    cpu.step().unwrap();
+    assert_eq!(0xFF, cpu.registers.y);
+    cpu.registers.flags.insert(FLAG_XY16);
+    cpu.registers.x = 0xFF;
+    cpu.step().unwrap();
+    assert_eq!(0x100, cpu.registers.x);
+}
+
+#[test]
+fn eight_bit_index_mode_clears_high_bytes() {
+    let bytes = create_rom(&[]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    let flags = cpu.registers.flags | FLAG_XY16;
+    cpu.registers.set_flags(flags);
+    cpu.registers.x = 0x1234;
+    cpu.registers.y = 0x5678;
+    cpu.registers.set_flags(flags - FLAG_XY16);
+    assert_eq!(0x34, cpu.registers.x);
+    assert_eq!(0x78, cpu.registers.y);
+}
+
+#[test]
+fn sixteen_bit_data_access_crosses_banks() {
+    // LDA $FFFF,X; STA $FFFF
+    let bytes = create_rom(&[0xBD, 0xFF, 0xFF, 0x8D, 0xFF, 0xFF]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags.insert(FLAG_A16);
+    cpu.registers.db = 0x7E;
+    cpu.bus.wram[0xFFFF] = 0x34;
+    cpu.bus.wram[0x1_0000] = 0x12;
+    cpu.step().unwrap();
+    assert_eq!(0x1234, cpu.registers.a);
+    cpu.registers.a = 0xABCD;
+    cpu.step().unwrap();
+    assert_eq!(0xCD, cpu.bus.wram[0xFFFF]);
+    assert_eq!(0xAB, cpu.bus.wram[0x1_0000]);
+}
+
+#[test]
+fn direct_page_wraps_within_bank_zero() {
+    // LDA $FF
+    let mut bytes = create_rom(&[0xA5, 0xFF]);
+    bytes[0x7FFF] = 0x78;
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags.insert(FLAG_A16);
+    cpu.registers.dp = 0xFF00;
+    cpu.bus.wram[0x0000] = 0x56;
+    cpu.step().unwrap();
+    assert_eq!(0x5678, cpu.registers.a);
+}
+
+#[test]
+fn direct_page_penalty() {
+    // LDA $10; LDA $10
+    let bytes = create_rom(&[0xA5, 0x10, 0xA5, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    let start = cpu.cycles;
+    cpu.step().unwrap();
+    let aligned = cpu.cycles - start;
+    cpu.registers.dp = 0x0001;
+    cpu.step().unwrap();
+    assert_eq!(aligned * 2 + 6, cpu.cycles - start);
+}
+
+#[test]
+fn jmp_indirect() {
+    // JMP ($0010,X)
+    let bytes = create_rom(&[0x7C, 0x10, 0x00]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.x = 2;
+    cpu.bus.wram[0x12..0x14].copy_from_slice(&[0x34, 0x12]);
+    cpu.step().unwrap();
+    assert_eq!(0x1234, cpu.registers.pc);
+}
+
+fn run_adc_sbc(opcode: u8, a: u16, operand: u16, flags: Flags) -> (u16, Flags) {
+    let [low, high] = [operand as u8, (operand >> 8) as u8];
+    let bytes = create_rom(&[opcode, low, high]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.a = a;
+    cpu.registers.flags = flags;
+    cpu.step().unwrap();
+    (cpu.registers.a, cpu.registers.flags)
+}
+
+#[test]
+fn adc_binary() {
+    let (a, flags) = run_adc_sbc(0x69, 0x7F, 0x01, Flags::empty());
+    assert_eq!(0x80, a);
+    assert_eq!(FLAG_OVERFLOW | FLAG_NEGATIVE, flags);
+
+    let (a, flags) = run_adc_sbc(0x69, 0x12FF, 0x01, FLAG_CARRY);
+    assert_eq!(0x1201, a);
+    assert_eq!(FLAG_CARRY, flags);
+
+    let (a, flags) = run_adc_sbc(0x69, 0xFFFF, 0x0001, FLAG_A16);
+    assert_eq!(0x0000, a);
+    assert_eq!(FLAG_A16 | FLAG_CARRY | FLAG_ZERO, flags);
+
+    let (a, flags) = run_adc_sbc(0x69, 0x7FFF, 0x0001, FLAG_A16);
+    assert_eq!(0x8000, a);
+    assert_eq!(FLAG_A16 | FLAG_OVERFLOW | FLAG_NEGATIVE, flags);
+}
+
+#[test]
+fn sbc_binary() {
+    let (a, flags) = run_adc_sbc(0xE9, 0x05, 0x03, FLAG_CARRY);
+    assert_eq!(0x02, a);
+    assert_eq!(FLAG_CARRY, flags);
+
+    let (a, flags) = run_adc_sbc(0xE9, 0x80, 0x01, FLAG_CARRY);
+    assert_eq!(0x7F, a);
+    assert_eq!(FLAG_CARRY | FLAG_OVERFLOW, flags);
+
+    let (a, flags) = run_adc_sbc(0xE9, 0x0000, 0x0001, FLAG_A16 | FLAG_CARRY);
+    assert_eq!(0xFFFF, a);
+    assert_eq!(FLAG_A16 | FLAG_NEGATIVE, flags);
+}
+
+#[test]
+fn adc_decimal() {
+    let (a, flags) = run_adc_sbc(0x69, 0x58, 0x46, FLAG_DECIMAL | FLAG_CARRY);
+    assert_eq!(0x05, a);
+    // Overflow is computed before adjusting the high digit.
+    assert_eq!(FLAG_DECIMAL | FLAG_CARRY | FLAG_OVERFLOW, flags);
+
+    let (a, flags) = run_adc_sbc(0x69, 0x79, 0x00, FLAG_DECIMAL | FLAG_CARRY);
+    assert_eq!(0x80, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_OVERFLOW | FLAG_NEGATIVE, flags);
+
+    let (a, flags) = run_adc_sbc(0x69, 0x1234, 0x8766, FLAG_DECIMAL | FLAG_A16);
+    assert_eq!(0x0000, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_A16 | FLAG_CARRY | FLAG_ZERO, flags);
+
+    let (a, flags) = run_adc_sbc(0x69, 0x0999, 0x0001, FLAG_DECIMAL | FLAG_A16);
+    assert_eq!(0x1000, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_A16, flags);
+    // Invalid BCD, where the adjusted low digit goes past 0x1F
+    let (a, flags) = run_adc_sbc(0x69, 0x0F, 0x0F, FLAG_DECIMAL | FLAG_CARRY);
+    assert_eq!(0x15, a);
+    assert_eq!(FLAG_DECIMAL, flags);
+}
+
+#[test]
+fn sbc_decimal() {
+    let (a, flags) = run_adc_sbc(0xE9, 0x50, 0x01, FLAG_DECIMAL | FLAG_CARRY);
+    assert_eq!(0x49, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_CARRY, flags);
+
+    let (a, flags) = run_adc_sbc(0xE9, 0x00, 0x01, FLAG_DECIMAL | FLAG_CARRY);
+    assert_eq!(0x99, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_NEGATIVE, flags);
+
+    let (a, flags) = run_adc_sbc(0xE9, 0x1000, 0x0001, FLAG_DECIMAL | FLAG_A16 | FLAG_CARRY);
+    assert_eq!(0x0999, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_A16 | FLAG_CARRY, flags);
+    // Invalid BCD, where the adjusted low digit goes below zero
+    let (a, flags) = run_adc_sbc(0xE9, 0x00, 0x0F, FLAG_DECIMAL);
+    assert_eq!(0x9A, a);
+    assert_eq!(FLAG_DECIMAL | FLAG_NEGATIVE, flags);
+}
+
+fn zero_negative_after(program: &[u8], flags: Flags, a: u16, x: u16) -> Flags {
+    let bytes = create_rom(program);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags = flags;
+    cpu.registers.a = a;
+    cpu.registers.x = x;
+    cpu.step().unwrap();
+    cpu.registers.flags & (FLAG_ZERO | FLAG_NEGATIVE)
+}
+
+#[test]
+fn zero_and_negative_flags() {
+    let none = Flags::empty();
+    let cases: &[(&[u8], Flags, u16, u16, Flags)] = &[
+        // LDA #
+        (&[0xA9, 0x00, 0x00], none, 0, 0, FLAG_ZERO),
+        (&[0xA9, 0x7F, 0x00], none, 0, 0, none),
+        (&[0xA9, 0x80, 0x00], none, 0, 0, FLAG_NEGATIVE),
+        (&[0xA9, 0x00, 0x00], FLAG_A16, 0, 0, FLAG_ZERO),
+        (&[0xA9, 0x80, 0x00], FLAG_A16, 0, 0, none),
+        (&[0xA9, 0x00, 0x80], FLAG_A16, 0, 0, FLAG_NEGATIVE),
+        // LDX #
+        (&[0xA2, 0x00, 0x01], none, 0, 0, FLAG_ZERO),
+        (&[0xA2, 0x00, 0x01], FLAG_XY16, 0, 0, none),
+        (&[0xA2, 0xFF, 0xFF], FLAG_XY16, 0, 0, FLAG_NEGATIVE),
+        // TAX
+        (&[0xAA], none, 0x0100, 0, FLAG_ZERO),
+        (&[0xAA], FLAG_XY16, 0x0100, 0, none),
+        (&[0xAA], none, 0x00FF, 0, FLAG_NEGATIVE),
+        (&[0xAA], FLAG_XY16, 0x8000, 0, FLAG_NEGATIVE),
+        // TXA
+        (&[0x8A], none, 0x1234, 0x80, FLAG_NEGATIVE),
+        (&[0x8A], FLAG_A16, 0x1234, 0x80, none),
+        // INX
+        (&[0xE8], none, 0, 0xFF, FLAG_ZERO),
+        (&[0xE8], FLAG_XY16, 0, 0xFF, none),
+        (&[0xE8], FLAG_XY16, 0, 0xFFFF, FLAG_ZERO),
+        // DEX
+        (&[0xCA], none, 0, 0x00, FLAG_NEGATIVE),
+        (&[0xCA], FLAG_XY16, 0, 0x00, FLAG_NEGATIVE),
+        (&[0xCA], FLAG_XY16, 0, 0x0100, none),
+        // TSC always transfers 16 bits
+        (&[0x3B], none, 0, 0, none),
+        // AND #
+        (&[0x29, 0x0F, 0x00], none, 0x00F0, 0, FLAG_ZERO),
+        (&[0x29, 0x00, 0x80], FLAG_A16, 0x8000, 0, FLAG_NEGATIVE),
+        // ASL A
+        (&[0x0A], none, 0x0040, 0, FLAG_NEGATIVE),
+        (&[0x0A], FLAG_A16, 0x0040, 0, none),
+        (&[0x0A], FLAG_A16, 0x8000, 0, FLAG_ZERO),
+        // INC A
+        (&[0x1A], none, 0x01FF, 0, FLAG_ZERO),
+        (&[0x1A], FLAG_A16, 0x01FF, 0, none),
+        // CMP #
+        (&[0xC9, 0x01, 0x00], none, 0x0100, 0, FLAG_NEGATIVE),
+        (&[0xC9, 0x00, 0x01], FLAG_A16, 0x0100, 0, FLAG_ZERO),
+        // CPX #
+        (&[0xE0, 0x01, 0x00], FLAG_XY16, 0, 0x0100, none),
+        // XBA uses the new low byte
+        (&[0xEB], FLAG_A16, 0x8000, 0, FLAG_NEGATIVE),
+        (&[0xEB], FLAG_A16, 0x0080, 0, FLAG_ZERO),
+    ];
+    for &(program, flags, a, x, expected) in cases {
+        assert_eq!(expected,
+                   zero_negative_after(program, flags, a, x),
+                   "program {:02X?} with flags {:?}",
+                   program,
+                   flags);
+    }
+}
+
+#[test]
+fn conditional_branches() {
+    // BEQ +2; BNE +2; (skipped) NOP NOP; BCS -8
+    let bytes = create_rom(&[0xF0, 0x02, 0xD0, 0x02, 0xEA, 0xEA, 0xB0, 0xF8]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!(0x8002, cpu.registers.pc);
+    cpu.step().unwrap();
+    assert_eq!(0x8006, cpu.registers.pc);
+    cpu.registers.flags.insert(FLAG_CARRY);
+    cpu.step().unwrap();
+    assert_eq!(0x8000, cpu.registers.pc);
+}
+
+#[test]
+fn brl() {
+    // BRL -3
+    let bytes = create_rom(&[0x82, 0xFD, 0xFF]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    for _ in 0..10 {
+        cpu.step().unwrap();
+        assert_eq!(0x8000, cpu.registers.pc);
+    }
+}
+
+#[test]
+fn jsr_rts() {
+    // JSR $8010
+    let mut bytes = create_rom(&[0x20, 0x10, 0x80]);
+    // RTS
+    bytes[0x10] = 0x60;
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!(0x8010, cpu.registers.pc);
+    assert_eq!(0x1FD, cpu.registers.sp);
+    // Address of the last byte of JSR
+    assert_eq!(&[0x02, 0x80], &cpu.bus.wram[0x1FE..0x200]);
+    cpu.step().unwrap();
+    assert_eq!(0x8003, cpu.registers.pc);
+    assert_eq!(0x1FF, cpu.registers.sp);
+}
+
+#[test]
+fn jsl_rtl() {
+    // JSL $01:8000
+    let mut bytes = create_rom(&[0x22, 0x00, 0x80, 0x01]);
+    // RTL
+    bytes[0x8000] = 0x6B;
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!((0x01, 0x8000), (cpu.registers.pb, cpu.registers.pc));
+    assert_eq!(&[0x03, 0x80, 0x00], &cpu.bus.wram[0x1FD..0x200]);
+    cpu.step().unwrap();
+    assert_eq!((0x00, 0x8004), (cpu.registers.pb, cpu.registers.pc));
+    assert_eq!(0x1FF, cpu.registers.sp);
+}
+
+#[test]
+fn jml() {
+    // JML $01:8000
+    let bytes = create_rom(&[0x5C, 0x00, 0x80, 0x01]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!((0x01, 0x8000), (cpu.registers.pb, cpu.registers.pc));
+}
+
+#[test]
+fn push_pull_sixteen_bits() {
+    // PHA; PLX
+    let bytes = create_rom(&[0x48, 0xFA]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags.insert(FLAG_A16 | FLAG_XY16);
+    cpu.registers.sp = 0x1FFF;
+    cpu.registers.a = 0x8034;
+    cpu.step().unwrap();
+    assert_eq!(0x1FFD, cpu.registers.sp);
+    assert_eq!(&[0x34, 0x80], &cpu.bus.wram[0x1FFE..0x2000]);
+    cpu.step().unwrap();
+    assert_eq!(0x1FFF, cpu.registers.sp);
+    assert_eq!(0x8034, cpu.registers.x);
+    assert!(cpu.registers.flags.contains(FLAG_NEGATIVE));
+}
+
+#[test]
+fn php_plp() {
+    // PHP; PLP
+    let bytes = create_rom(&[0x08, 0x28]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags = FLAG_CARRY | FLAG_XY16;
+    cpu.step().unwrap();
+    // M bit is set for 8-bit accumulator, X bit is clear for 16-bit index
+    assert_eq!(0x21, cpu.bus.wram[0x1FF]);
+    cpu.bus.wram[0x1FF] = 0x30;
+    cpu.registers.x = 0x1234;
+    cpu.step().unwrap();
+    assert_eq!(Flags::empty(), cpu.registers.flags);
+    assert_eq!(0x34, cpu.registers.x);
+}
+
+#[test]
+fn emulation_mode_stack_wraps_in_page_one() {
+    // PHA; PLA
+    let bytes = create_rom(&[0x48, 0x68]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.emulation = true;
+    cpu.registers.sp = 0x0100;
+    cpu.registers.a = 0x42;
+    cpu.step().unwrap();
+    assert_eq!(0x01FF, cpu.registers.sp);
+    assert_eq!(0x42, cpu.bus.wram[0x0100]);
+    cpu.step().unwrap();
+    assert_eq!(0x0100, cpu.registers.sp);
+}
+
+#[test]
+fn new_instructions_leave_page_one_in_emulation_mode() {
+    // PEA $1234
+    let bytes = create_rom(&[0xF4, 0x34, 0x12]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.emulation = true;
+    cpu.registers.sp = 0x0100;
+    cpu.step().unwrap();
+    assert_eq!(&[0x34, 0x12], &cpu.bus.wram[0x00FF..0x0101]);
+    assert_eq!(0x01FE, cpu.registers.sp);
+}
+
+#[test]
+fn per() {
+    // PER $0010
+    let bytes = create_rom(&[0x62, 0x10, 0x00]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!(&[0x13, 0x80], &cpu.bus.wram[0x1FE..0x200]);
+}
+
+#[test]
+fn power_on_in_emulation_mode() {
+    // CLC; XCE; REP #$30; LDA #$1234; SEC; XCE
+    let bytes = create_rom(&[0x18, 0xFB, 0xC2, 0x30, 0xA9, 0x34, 0x12, 0x38, 0xFB]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    assert!(cpu.registers.emulation);
+    assert_eq!(0x01FF, cpu.registers.sp);
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert!(!cpu.registers.emulation);
+    assert!(cpu.registers.flags.contains(FLAG_CARRY));
+    cpu.step().unwrap();
+    assert!(cpu.registers.flags.contains(FLAG_A16 | FLAG_XY16));
+    cpu.step().unwrap();
+    assert_eq!(0x1234, cpu.registers.a);
+    cpu.registers.sp = 0x1FF0;
+    cpu.registers.x = 0x1234;
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert!(cpu.registers.emulation);
+    assert!(!cpu.registers.flags.intersects(FLAG_A16 | FLAG_XY16));
+    assert_eq!(0x01F0, cpu.registers.sp);
+    assert_eq!(0x0034, cpu.registers.x);
+}
+
+#[test]
+fn rep_sep_in_emulation_mode() {
+    // REP #$39; SEP #$08
+    let bytes = create_rom(&[0xC2, 0x39, 0xE2, 0x08]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.flags.insert(FLAG_CARRY);
+    cpu.step().unwrap();
+    // M and X bits can't be changed in emulation mode
+    assert_eq!(FLAG_NO_IRQ, cpu.registers.flags);
+    cpu.step().unwrap();
+    assert_eq!(FLAG_NO_IRQ | FLAG_DECIMAL, cpu.registers.flags);
+}
+
+#[test]
+fn emulation_mode_direct_page_wraps_in_page() {
+    // LDA $FF,X; LDA ($FF)
+    let bytes = create_rom(&[0xB5, 0xFF, 0xB2, 0xFF]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.x = 0x02;
+    cpu.registers.db = 0x7E;
+    cpu.bus.wram[0x01] = 0x12;
+    cpu.bus.wram[0xFF] = 0x00;
+    cpu.bus.wram[0x00] = 0x03;
+    cpu.bus.wram[0x0300] = 0x34;
+    cpu.step().unwrap();
+    assert_eq!(0x12, cpu.registers.a);
+    cpu.step().unwrap();
+    assert_eq!(0x34, cpu.registers.a);
+}
+
+#[test]
+fn mvn() {
+    // MVN $7E,$7F
+    let bytes = create_rom(&[0x54, 0x7F, 0x7E]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags.insert(FLAG_XY16);
+    cpu.registers.a = 2;
+    cpu.registers.x = 0x1000;
+    cpu.registers.y = 0x2000;
+    cpu.bus.wram[0x1000..0x1003].copy_from_slice(&[1, 2, 3]);
+    // Block moves copy one byte per instruction
+    cpu.step().unwrap();
+    assert_eq!(0x8000, cpu.registers.pc);
+    assert_eq!(0x7F, cpu.registers.db);
+    assert_eq!(1, cpu.bus.wram[0x1_2000]);
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(0x8003, cpu.registers.pc);
+    assert_eq!(&[1, 2, 3], &cpu.bus.wram[0x1_2000..0x1_2003]);
+    assert_eq!((0xFFFF, 0x1003, 0x2003),
+               (cpu.registers.a, cpu.registers.x, cpu.registers.y));
+}
+
+#[test]
+fn mvp_with_eight_bit_index() {
+    // MVP $7E,$7E
+    let bytes = create_rom(&[0x44, 0x7E, 0x7E]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 1;
+    cpu.registers.x = 0x00;
+    cpu.registers.y = 0x80;
+    cpu.bus.wram[0x00] = 5;
+    cpu.bus.wram[0xFF] = 6;
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(&[6, 5], &cpu.bus.wram[0x7F..0x81]);
+    assert_eq!((0xFE, 0x7E), (cpu.registers.x, cpu.registers.y));
+}
+
+#[test]
+fn shifts_and_rotates() {
+    // ASL A; ROL A; LSR A; ROR A
+    let bytes = create_rom(&[0x0A, 0x2A, 0x4A, 0x6A]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x1281;
+    cpu.step().unwrap();
+    assert_eq!(0x1202, cpu.registers.a);
+    assert!(cpu.registers.flags.contains(FLAG_CARRY));
+    cpu.step().unwrap();
+    assert_eq!(0x1205, cpu.registers.a);
+    assert!(!cpu.registers.flags.contains(FLAG_CARRY));
+    cpu.step().unwrap();
+    assert_eq!(0x1202, cpu.registers.a);
+    assert!(cpu.registers.flags.contains(FLAG_CARRY));
+    cpu.step().unwrap();
+    assert_eq!(0x1281, cpu.registers.a);
+    assert!(!cpu.registers.flags.contains(FLAG_CARRY));
+}
+
+#[test]
+fn read_modify_write_sixteen_bits() {
+    // ROR $10; INC $0012; DEC $10,X
+    let bytes = create_rom(&[0x66, 0x10, 0xEE, 0x12, 0x00, 0xD6, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags = FLAG_A16 | FLAG_CARRY;
+    cpu.registers.x = 4;
+    cpu.bus.wram[0x10..0x16].copy_from_slice(&[0x01, 0x00, 0xFF, 0x00, 0x00, 0x00]);
+    cpu.step().unwrap();
+    assert_eq!(&[0x00, 0x80], &cpu.bus.wram[0x10..0x12]);
+    assert!(cpu.registers.flags.contains(FLAG_CARRY | FLAG_NEGATIVE));
+    cpu.step().unwrap();
+    assert_eq!(&[0x00, 0x01], &cpu.bus.wram[0x12..0x14]);
+    cpu.step().unwrap();
+    assert_eq!(&[0xFF, 0xFF], &cpu.bus.wram[0x14..0x16]);
+    assert!(cpu.registers.flags.contains(FLAG_NEGATIVE));
+}
+
+#[test]
+fn logic_instructions() {
+    // ORA #$0F; EOR #$FF; AND $10
+    let bytes = create_rom(&[0x09, 0x0F, 0x49, 0xFF, 0x25, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x1230;
+    cpu.bus.wram[0x10] = 0x40;
+    cpu.step().unwrap();
+    assert_eq!(0x123F, cpu.registers.a);
+    cpu.step().unwrap();
+    assert_eq!(0x12C0, cpu.registers.a);
+    assert!(cpu.registers.flags.contains(FLAG_NEGATIVE));
+    cpu.step().unwrap();
+    assert_eq!(0x1240, cpu.registers.a);
+    assert!(!cpu.registers.flags.contains(FLAG_NEGATIVE));
+}
+
+#[test]
+fn bit() {
+    // BIT $10; BIT #$C0
+    let bytes = create_rom(&[0x24, 0x10, 0x89, 0x01]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x01;
+    cpu.bus.wram[0x10] = 0xC0;
+    cpu.step().unwrap();
+    assert_eq!(FLAG_NO_IRQ | FLAG_NEGATIVE | FLAG_OVERFLOW | FLAG_ZERO,
+               cpu.registers.flags);
+    // Immediate BIT only changes the zero flag
+    cpu.step().unwrap();
+    assert_eq!(FLAG_NO_IRQ | FLAG_NEGATIVE | FLAG_OVERFLOW, cpu.registers.flags);
+}
+
+#[test]
+fn tsb_trb() {
+    // TSB $10; TRB $10
+    let bytes = create_rom(&[0x04, 0x10, 0x14, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x03;
+    cpu.bus.wram[0x10] = 0x84;
+    cpu.step().unwrap();
+    assert_eq!(0x87, cpu.bus.wram[0x10]);
+    assert!(cpu.registers.flags.contains(FLAG_ZERO));
+    cpu.step().unwrap();
+    assert_eq!(0x84, cpu.bus.wram[0x10]);
+    assert!(!cpu.registers.flags.contains(FLAG_ZERO));
+}
+
+#[test]
+fn compare() {
+    // CMP #$40; CPX #$40; CPY $10
+    let bytes = create_rom(&[0xC9, 0x40, 0xE0, 0x40, 0xC4, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x40;
+    cpu.registers.x = 0x30;
+    cpu.registers.y = 0x50;
+    cpu.bus.wram[0x10] = 0x40;
+    cpu.step().unwrap();
+    assert!(cpu.registers.flags.contains(FLAG_CARRY | FLAG_ZERO));
+    cpu.step().unwrap();
+    assert!(!cpu.registers.flags.intersects(FLAG_CARRY | FLAG_ZERO));
+    assert!(cpu.registers.flags.contains(FLAG_NEGATIVE));
+    cpu.step().unwrap();
+    assert!(cpu.registers.flags.contains(FLAG_CARRY));
+    assert!(!cpu.registers.flags.intersects(FLAG_ZERO | FLAG_NEGATIVE));
+}
+
+#[test]
+fn brk_rti_native() {
+    // BRK #$EE
+    let mut bytes = create_rom(&[0x00, 0xEE]);
+    bytes[0x7FE6..0x7FE8].copy_from_slice(&[0x00, 0x90]);
+    // RTI
+    bytes[0x1000] = 0x40;
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags = FLAG_DECIMAL | FLAG_CARRY;
+    cpu.step().unwrap();
+    assert_eq!(0x9000, cpu.registers.pc);
+    assert_eq!(0x1FB, cpu.registers.sp);
+    assert_eq!(&[0x39, 0x02, 0x80, 0x00], &cpu.bus.wram[0x1FC..0x200]);
+    assert_eq!(FLAG_NO_IRQ | FLAG_CARRY, cpu.registers.flags);
+    cpu.step().unwrap();
+    assert_eq!(0x8002, cpu.registers.pc);
+    assert_eq!(0x1FF, cpu.registers.sp);
+    assert_eq!(FLAG_DECIMAL | FLAG_CARRY, cpu.registers.flags);
+}
+
+#[test]
+fn cop_emulation_mode() {
+    // COP #$00
+    let mut bytes = create_rom(&[0x02, 0x00]);
+    bytes[0x7FF4..0x7FF6].copy_from_slice(&[0x34, 0x92]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!(0x9234, cpu.registers.pc);
+    assert_eq!(0x1FC, cpu.registers.sp);
+    // Only BRK sets the break flag
+    assert_eq!(&[0x24, 0x02, 0x80], &cpu.bus.wram[0x1FD..0x200]);
+}
+
+#[test]
+fn brk_emulation_mode_sets_break_flag() {
+    let mut bytes = create_rom(&[0x00, 0x00]);
+    bytes[0x7FFE..0x8000].copy_from_slice(&[0x00, 0x90]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.step().unwrap();
+    assert_eq!(0x9000, cpu.registers.pc);
+    assert_eq!(0x34, cpu.bus.wram[0x1FD]);
+}
+
+#[test]
+fn hardware_interrupts() {
+    // CLC; CLI
+    let mut bytes = create_rom(&[0x18, 0x58]);
+    // NMI handler at $A000 returns right away
+    bytes[0x2000] = 0x40;
+    bytes[0x7FFA..0x7FFC].copy_from_slice(&[0x00, 0xA0]);
+    bytes[0x7FFE..0x8000].copy_from_slice(&[0x00, 0xB0]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.irq = true;
+    // IRQ is masked
+    cpu.step().unwrap();
+    assert_eq!(0x8001, cpu.registers.pc);
+    cpu.step().unwrap();
+    assert_eq!(0x8002, cpu.registers.pc);
+    cpu.nmi = true;
+    // NMI takes priority over IRQ
+    cpu.step().unwrap();
+    assert_eq!(0xA000, cpu.registers.pc);
+    assert!(!cpu.nmi);
+    assert_eq!(&[0x20, 0x02, 0x80], &cpu.bus.wram[0x1FD..0x200]);
+    assert!(cpu.registers.flags.contains(FLAG_NO_IRQ));
+    // Returning from NMI clears interrupt disable flag, so IRQ runs
+    cpu.step().unwrap();
+    assert_eq!(0x8002, cpu.registers.pc);
+    assert!(!cpu.registers.flags.contains(FLAG_NO_IRQ));
+    cpu.step().unwrap();
+    assert_eq!(0xB000, cpu.registers.pc);
+}
+
+#[test]
+fn native_interrupt_vectors() {
+    let mut bytes = create_rom(&[]);
+    bytes[0x7FEA..0x7FEC].copy_from_slice(&[0x00, 0xA0]);
+    bytes[0x7FEE..0x7FF0].copy_from_slice(&[0x00, 0xB0]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.pb = 0x7E;
+    cpu.registers.pc = 0x1234;
+    cpu.registers.flags = Flags::empty();
+    cpu.irq = true;
+    cpu.step().unwrap();
+    assert_eq!((0x00, 0xB000), (cpu.registers.pb, cpu.registers.pc));
+    assert_eq!(&[0x30, 0x34, 0x12, 0x7E], &cpu.bus.wram[0x1FC..0x200]);
+    cpu.nmi = true;
+    cpu.step().unwrap();
+    assert_eq!(0xA000, cpu.registers.pc);
+    assert_eq!(0xFFEA, Interrupt::Nmi.vector(false));
+    assert_eq!(0xFFE8, Interrupt::Abort.vector(false));
+    assert_eq!(0xFFF8, Interrupt::Abort.vector(true));
+}
+
+#[test]
+fn reset() {
+    let bytes = create_rom(&[]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.set_emulation(false);
+    cpu.registers.flags = FLAG_A16 | FLAG_XY16 | FLAG_DECIMAL;
+    cpu.registers.sp = 0x1234;
+    cpu.registers.x = 0x1234;
+    cpu.registers.dp = 0x4300;
+    cpu.registers.pb = 0x12;
+    cpu.registers.pc = 0x3456;
+    cpu.nmi = true;
+    cpu.reset();
+    assert!(cpu.registers.emulation);
+    assert_eq!(FLAG_NO_IRQ, cpu.registers.flags);
+    assert_eq!((0x134, 0x34, 0), (cpu.registers.sp, cpu.registers.x, cpu.registers.dp));
+    assert_eq!((0x00, 0x8000), (cpu.registers.pb, cpu.registers.pc));
+    assert!(!cpu.nmi);
+}
+
+#[test]
+fn wai() {
+    // WAI; CLC; WAI
+    let mut bytes = create_rom(&[0xCB, 0x18, 0xCB]);
+    bytes[0x7FFE..0x8000].copy_from_slice(&[0x00, 0xB0]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.flags.insert(FLAG_CARRY);
+    assert_eq!(State::Waiting, cpu.step().unwrap());
+    assert_eq!(State::Waiting, cpu.step().unwrap());
+    assert_eq!(0x8001, cpu.registers.pc);
+    // Masked IRQ resumes execution without running the handler
+    cpu.irq = true;
+    assert_eq!(State::Running, cpu.step().unwrap());
+    assert_eq!(0x8002, cpu.registers.pc);
+    assert!(!cpu.registers.flags.contains(FLAG_CARRY));
+    cpu.irq = false;
+    assert_eq!(State::Waiting, cpu.step().unwrap());
+    cpu.registers.flags.remove(FLAG_NO_IRQ);
+    cpu.irq = true;
+    assert_eq!(State::Running, cpu.step().unwrap());
+    assert_eq!(0xB000, cpu.registers.pc);
+}
+
+#[test]
+fn stp() {
+    let bytes = create_rom(&[0xDB]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    assert_eq!(State::Stopped, cpu.step().unwrap());
+    cpu.nmi = true;
+    cpu.irq = true;
+    assert_eq!(State::Stopped, cpu.step().unwrap());
+    assert_eq!(0x8001, cpu.registers.pc);
+    cpu.reset();
+    assert_eq!(State::Running, cpu.state);
+    assert_eq!(0x8000, cpu.registers.pc);
+}
+
+#[test]
+fn unmapped_write() {
+    // STA $808000; STA $808001
+    let bytes = create_rom(&[0x8F, 0x00, 0x80, 0x80, 0x8F, 0x01, 0x80, 0x80]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.registers.a = 0x12;
+    let error = Error::UnmappedWrite {
+        pb: 0x00,
+        pc: 0x8000,
+        bank: 0x80,
+        address: 0x8000,
+        value: 0x12,
+    };
+    assert_eq!(Err(error), cpu.step());
+    assert_eq!("write of 12 to unmapped address 80:8000 at 00:8000",
+               error.to_string());
+    // The instruction still finishes
+    assert_eq!(0x8004, cpu.registers.pc);
+    cpu.error_policy = ErrorPolicy::Skip;
+    assert_eq!(Ok(State::Running), cpu.step());
+}
+
+#[test]
+fn open_bus() {
+    // LDA $2000; LDA ($10)
+    let bytes = create_rom(&[0xAD, 0x00, 0x20, 0xB2, 0x10]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    cpu.bus.wram[0x10..0x12].copy_from_slice(&[0x00, 0x21]);
+    cpu.step().unwrap();
+    // The last value on the bus was the high byte of the address
+    assert_eq!(0x20, cpu.registers.a);
+    cpu.step().unwrap();
+    assert_eq!(0x21, cpu.registers.a);
+}
+
+#[test]
+fn master_cycles() {
+    // CLC; LDA $10; LDA $2140; LDA $4016; LDA #$01; STA $420D; JML $808000
+    let bytes = create_rom(&[0x18,
+                             0xA5, 0x10,
+                             0xAD, 0x40, 0x21,
+                             0xAD, 0x16, 0x40,
+                             0xA9, 0x01,
+                             0x8D, 0x0D, 0x42,
+                             0x5C, 0x00, 0x80, 0x80]);
+    let mut cpu = CPU::new(LoROM::new(&bytes));
+    let mut elapsed = || {
+        let start = cpu.cycles;
+        cpu.step().unwrap();
+        cpu.cycles - start
+    };
+    assert_eq!(8 + 6, elapsed());
+    assert_eq!(8 + 8 + 8, elapsed());
+    assert_eq!(8 * 3 + 6, elapsed());
+    assert_eq!(8 * 3 + 12, elapsed());
+    assert_eq!(8 * 2, elapsed());
+    assert_eq!(8 * 3 + 6, elapsed());
+    assert_eq!(8 * 4, elapsed());
+    // FastROM is enabled now
+    assert_eq!(6 + 6, elapsed());
+}
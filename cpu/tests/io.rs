@@ -0,0 +1,162 @@
+extern crate snesemu_cpu;
+
+use snesemu_cpu::cpu::{CPU, State};
+use snesemu_cpu::io::{Io, CYCLES_PER_FRAME, CYCLES_PER_LINE, VBLANK_LINE};
+use snesemu_cpu::mapper::LoROM;
+
+/// Runs CPU cycles of 6 master cycles each.
+fn run(io: &mut Io, now: &mut u64, cycles: u32) {
+    for _ in 0..cycles {
+        *now += 6;
+        io.tick(*now);
+    }
+}
+
+fn result(io: &mut Io, address: u16) -> u16 {
+    io.read(address, 0) as u16 | (io.read(address + 1, 0) as u16) << 8
+}
+
+#[test]
+fn multiply() {
+    let (mut io, mut now) = (Io::new(), 0);
+    io.write(0x4202, 200);
+    io.write(0x4203, 150);
+    run(&mut io, &mut now, 7);
+    assert!(result(&mut io, 0x4216) != 30000);
+    run(&mut io, &mut now, 1);
+    assert_eq!(30000, result(&mut io, 0x4216));
+    // RDDIV holds the multiplier afterwards
+    assert_eq!(150, result(&mut io, 0x4214));
+}
+
+#[test]
+fn divide() {
+    let (mut io, mut now) = (Io::new(), 0);
+    io.write(0x4204, 0xE8);
+    io.write(0x4205, 0x03);
+    io.write(0x4206, 7);
+    run(&mut io, &mut now, 15);
+    assert!(result(&mut io, 0x4214) != 142);
+    run(&mut io, &mut now, 1);
+    assert_eq!(142, result(&mut io, 0x4214));
+    assert_eq!(6, result(&mut io, 0x4216));
+    // Division by zero
+    io.write(0x4206, 0);
+    run(&mut io, &mut now, 16);
+    assert_eq!(0xFFFF, result(&mut io, 0x4214));
+    assert_eq!(1000, result(&mut io, 0x4216));
+}
+
+#[test]
+fn skipped_cycles() {
+    // Skipping over many cycles at once, as a halted processor does
+    let mut io = Io::new();
+    io.write(0x4202, 200);
+    io.write(0x4203, 150);
+    io.tick(7 * 6);
+    assert!(result(&mut io, 0x4216) != 30000);
+    io.tick(8 * 6);
+    assert_eq!(30000, result(&mut io, 0x4216));
+    io.write(0x4204, 0xE8);
+    io.write(0x4205, 0x03);
+    io.write(0x4206, 7);
+    io.tick(1000);
+    assert_eq!(142, result(&mut io, 0x4214));
+    assert_eq!(6, result(&mut io, 0x4216));
+}
+
+#[test]
+fn vblank() {
+    let mut io = Io::new();
+    let vblank = VBLANK_LINE * CYCLES_PER_LINE;
+    io.tick(vblank - 6);
+    assert_eq!(0x00, io.read(0x4212, 0) & 0x80);
+    assert_eq!(0x02, io.read(0x4210, 0));
+    io.tick(vblank);
+    // Disabled NMI still sets the flag of RDNMI
+    assert!(!io.nmi);
+    assert_eq!(0x80, io.read(0x4212, 0) & 0x80);
+    assert_eq!(0x82, io.read(0x4210, 0));
+    assert_eq!(0x02, io.read(0x4210, 0));
+    // NMI enabled in vertical blanking fires immediately
+    io.tick(CYCLES_PER_FRAME + vblank);
+    io.write(0x4200, 0x80);
+    assert!(io.nmi);
+    io.nmi = false;
+    io.tick(2 * CYCLES_PER_FRAME + vblank + 6);
+    assert!(io.nmi);
+    // The flag is cleared when vertical blanking ends
+    io.tick(3 * CYCLES_PER_FRAME);
+    assert_eq!(0x02, io.read(0x4210, 0));
+    assert_eq!(0x00, io.read(0x4212, 0) & 0x80);
+}
+
+#[test]
+fn timer_irq() {
+    let mut io = Io::new();
+    // H-IRQ at dot 100 of every scanline
+    io.write(0x4207, 100);
+    io.write(0x4208, 0);
+    io.write(0x4200, 0x10);
+    io.tick(99 * 4);
+    assert!(!io.timeup);
+    io.tick(100 * 4);
+    assert!(io.timeup);
+    assert_eq!(0x80, io.read(0x4211, 0));
+    assert!(!io.timeup);
+    io.tick(CYCLES_PER_LINE + 100 * 4 + 4);
+    assert!(io.timeup);
+    // Disabling timers clears the flag
+    io.write(0x4200, 0x00);
+    assert!(!io.timeup);
+    // HV-IRQ at dot 16 of scanline 300, which doesn't exist
+    io.write(0x4207, 16);
+    io.write(0x4209, 0x2C);
+    io.write(0x420A, 0x01);
+    io.write(0x4200, 0x30);
+    io.tick(CYCLES_PER_FRAME * 2);
+    assert!(!io.timeup);
+    // V-IRQ at the start of scanline 200
+    io.write(0x420A, 0x00);
+    io.write(0x4209, 200);
+    io.write(0x4200, 0x20);
+    io.tick(CYCLES_PER_FRAME * 2 + 200 * CYCLES_PER_LINE - 6);
+    assert!(!io.timeup);
+    io.tick(CYCLES_PER_FRAME * 2 + 200 * CYCLES_PER_LINE);
+    assert!(io.timeup);
+}
+
+#[test]
+fn nmi_wakes_up_wai() {
+    // LDA #$80; STA $4200; WAI; STP
+    let mut rom = vec![0; 0x8000];
+    rom[..7].copy_from_slice(&[0xA9, 0x80, 0x8D, 0x00, 0x42, 0xCB, 0xDB]);
+    // NMI handler at $8100 increments X
+    rom[0x100..0x102].copy_from_slice(&[0xE8, 0xDB]);
+    rom[0x7FFA..0x7FFE].copy_from_slice(&[0x00, 0x81, 0x00, 0x80]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    assert_eq!(State::Waiting, cpu.run_until(CYCLES_PER_LINE).unwrap());
+    assert_eq!(State::Stopped, cpu.run_until(CYCLES_PER_FRAME).unwrap());
+    assert_eq!(1, cpu.registers.x);
+    assert_eq!(0x8102, cpu.registers.pc);
+}
+
+#[test]
+fn timer_irq_line() {
+    // LDA #$10; STA $4200; CLI; WAI; LDA $4211; STP
+    let mut rom = vec![0; 0x8000];
+    rom[..11].copy_from_slice(&[0xA9, 0x10, 0x8D, 0x00, 0x42, 0x58, 0xCB, 0xAD, 0x11, 0x42,
+                                0xDB]);
+    // IRQ handler at $8100 acknowledges the timer and returns
+    rom[0x100..0x105].copy_from_slice(&[0xE8, 0xAD, 0x11, 0x42, 0x40]);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    rom[0x7FFE..0x8000].copy_from_slice(&[0x00, 0x81]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    cpu.bus.io.htime = 200;
+    assert_eq!(State::Stopped, cpu.run_until(CYCLES_PER_LINE).unwrap());
+    assert_eq!(1, cpu.registers.x);
+    assert!(!cpu.bus.io.timeup);
+    // The timer keeps running while stopped
+    cpu.run_until(CYCLES_PER_LINE * 2).unwrap();
+    assert!(cpu.bus.io.timeup);
+}
@@ -0,0 +1,151 @@
+extern crate snesemu_cpu;
+
+use std::cell::Cell;
+
+use snesemu_cpu::cpu::{CPU, Flags, FLAG_CARRY, FLAG_ZERO, FLAG_XY16, FLAG_A16, FLAG_OVERFLOW,
+                       FLAG_NEGATIVE};
+use snesemu_cpu::error::Error;
+use snesemu_cpu::mapper::{LoROM, Mapper};
+use snesemu_cpu::opcodes::{disassemble, table, trace};
+
+/// Mapper with the same value everywhere, which counts accesses.
+struct CountingMapper {
+    accesses: Cell<u64>,
+}
+
+impl Mapper for CountingMapper {
+    fn read(&self, _bank: u8, _address: u16) -> Option<u8> {
+        self.accesses.set(self.accesses.get() + 1);
+        Some(0x60)
+    }
+
+    fn write(&mut self, _bank: u8, _address: u16, _value: u8) -> Result<(), Error> {
+        self.accesses.set(self.accesses.get() + 1);
+        Ok(())
+    }
+}
+
+/// Flags which make a conditional branch not taken.
+fn not_taken(opcode: u8) -> Flags {
+    match opcode {
+        0x10 => FLAG_NEGATIVE,
+        0x50 => FLAG_OVERFLOW,
+        0x90 => FLAG_CARRY,
+        0xD0 => FLAG_ZERO,
+        _ => Flags::empty(),
+    }
+}
+
+#[test]
+fn table_cycles_match_execution() {
+    for &widths in &[Flags::empty(), FLAG_A16, FLAG_XY16, FLAG_A16 | FLAG_XY16] {
+        for opcode in 0..256 {
+            let opcode = opcode as u8;
+            let mut cpu = CPU::new(CountingMapper { accesses: Cell::new(0) });
+            cpu.registers.set_emulation(false);
+            cpu.registers.set_flags(widths | not_taken(opcode));
+            // Keep every access within the mapper, where accesses take 8
+            // master cycles.
+            cpu.registers.pc = 0x8000;
+            cpu.registers.dp = 0x6000;
+            cpu.registers.sp = 0x6100;
+            cpu.registers.db = 0x60;
+            let expected = table::<CountingMapper>(&cpu.registers)[opcode as usize].cycles;
+            // Run an opcode as if it was read from memory, the opcode fetch
+            // is counted separately.
+            let start = cpu.cycles;
+            cpu.bus.mapper.accesses.set(0);
+            let handler = table::<CountingMapper>(&cpu.registers)[opcode as usize].handler;
+            cpu.registers.pc = 0x8001;
+            handler(&mut cpu);
+            let accesses = cpu.bus.mapper.accesses.get();
+            let idle = (cpu.cycles - start - 8 * accesses) / 6;
+            assert_eq!(expected as u64,
+                       1 + accesses + idle,
+                       "opcode {:02X} with flags {:?}",
+                       opcode,
+                       widths);
+        }
+    }
+}
+
+#[test]
+fn emulation_mode_table() {
+    let mut cpu = CPU::new(CountingMapper { accesses: Cell::new(0) });
+    cpu.registers.set_emulation(false);
+    let native = table::<CountingMapper>(&cpu.registers);
+    cpu.registers.set_emulation(true);
+    let emulation = table::<CountingMapper>(&cpu.registers);
+    for opcode in 0..256 {
+        let extra = match opcode {
+            // BRK, COP and RTI don't push or pull the program bank.
+            0x00 | 0x02 | 0x40 => 1,
+            _ => 0,
+        };
+        assert_eq!(emulation[opcode].mnemonic, native[opcode].mnemonic);
+        assert_eq!(emulation[opcode].cycles + extra, native[opcode].cycles);
+    }
+}
+
+#[test]
+fn instruction_length() {
+    let mut cpu = CPU::new(CountingMapper { accesses: Cell::new(0) });
+    cpu.registers.set_emulation(false);
+    let lengths = |cpu: &CPU<CountingMapper>| {
+        let table = table::<CountingMapper>(&cpu.registers);
+        [0xA9, 0xA2, 0xC2, 0xEA, 0x22, 0x54]
+            .iter()
+            .map(|&opcode| table[opcode].length())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![2, 2, 2, 1, 4, 3], lengths(&cpu));
+    cpu.registers.set_flags(FLAG_A16);
+    assert_eq!(vec![3, 2, 2, 1, 4, 3], lengths(&cpu));
+    cpu.registers.set_flags(FLAG_XY16);
+    assert_eq!(vec![2, 3, 2, 1, 4, 3], lengths(&cpu));
+}
+
+fn disassemble_bytes(program: &[u8], flags: Flags) -> String {
+    let mut rom = [0; 0x8000];
+    rom[..program.len()].copy_from_slice(program);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    let mut cpu = CPU::new(LoROM::new(&rom));
+    cpu.registers.set_emulation(false);
+    cpu.registers.set_flags(flags);
+    disassemble(&cpu, 0x00, 0x8000)
+}
+
+#[test]
+fn disassembly() {
+    let none = Flags::empty();
+    let cases: &[(&[u8], Flags, &str)] = &[(&[0xEA], none, "NOP"),
+                                           (&[0x0A], none, "ASL A"),
+                                           (&[0xA9, 0x34, 0x12], none, "LDA #$34"),
+                                           (&[0xA9, 0x34, 0x12], FLAG_A16, "LDA #$1234"),
+                                           (&[0xA2, 0x34, 0x12], FLAG_A16, "LDX #$34"),
+                                           (&[0xC2, 0x30], FLAG_A16, "REP #$30"),
+                                           (&[0x9D, 0x34, 0x12], none, "STA $1234,X"),
+                                           (&[0xBF, 0x56, 0x34, 0x12], none, "LDA $123456,X"),
+                                           (&[0x7C, 0x34, 0x12], none, "JMP ($1234,X)"),
+                                           (&[0xDC, 0x34, 0x12], none, "JML [$1234]"),
+                                           (&[0xB6, 0x12], none, "LDX $12,Y"),
+                                           (&[0xA1, 0x12], none, "LDA ($12,X)"),
+                                           (&[0xB7, 0x12], none, "LDA [$12],Y"),
+                                           (&[0xB3, 0x12], none, "LDA ($12,S),Y"),
+                                           (&[0xD0, 0xFE], none, "BNE $8000"),
+                                           (&[0x82, 0x00, 0x10], none, "BRL $9003"),
+                                           (&[0x54, 0x7E, 0x7F], none, "MVN $7F,$7E")];
+    for &(program, flags, expected) in cases {
+        assert_eq!(expected, disassemble_bytes(program, flags));
+    }
+}
+
+#[test]
+fn trace_line() {
+    let mut rom = [0; 0x8000];
+    rom[..2].copy_from_slice(&[0xA9, 0x12]);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    let cpu = CPU::new(LoROM::new(&rom));
+    assert_eq!("00:8000 LDA #$12         A:0000 X:0000 Y:0000 S:01FF D:0000 DB:00 P:34 E:1",
+               trace(&cpu));
+}
@@ -0,0 +1,336 @@
+extern crate snesemu_cpu;
+
+use snesemu_cpu::buffer;
+use snesemu_cpu::bus::Device;
+use snesemu_cpu::io::VBLANK_LINE;
+use snesemu_cpu::ppu::Ppu;
+
+fn write_word(ppu: &mut Ppu, address: u8, value: u16) {
+    ppu.write(address, value as u8);
+    ppu.write(address + 1, (value >> 8) as u8);
+}
+
+/// Draws a whole frame, returning the first line as it appears in the
+/// frame buffer.
+fn first_row(ppu: &mut Ppu) -> Vec<u32> {
+    ppu.set_position(0, 0);
+    ppu.set_position(VBLANK_LINE, 0);
+    let mut frame = vec![0; buffer::WIDTH * buffer::HEIGHT];
+    ppu.draw_frame(&mut frame);
+    // The first line is centered, starting at row 16
+    frame[16 * buffer::WIDTH..17 * buffer::WIDTH].to_vec()
+}
+
+/// Draws a whole frame, returning the color of the first line at the
+/// start of every dot.
+fn first_line(ppu: &mut Ppu) -> Vec<u32> {
+    first_row(ppu).into_iter().step_by(2).collect()
+}
+
+/// Sets up mode 1 with BG1 showing tile 1 at the top left corner of its
+/// tilemap at $0400, with tiles at $1000 and red, green and blue colors.
+fn setup_mode_1(ppu: &mut Ppu) {
+    ppu.write(0x05, 0x01);
+    ppu.write(0x07, 0x04);
+    ppu.write(0x0B, 0x01);
+    ppu.write(0x2C, 0x01);
+    // The first line shows row 1 of backgrounds, scroll it to row 0
+    ppu.write(0x0E, 0xFF);
+    ppu.write(0x0E, 0x03);
+    ppu.vram[0x0400] = 0x0401;
+    // Color 1 at dot 0 and color 2 at dot 1 of tile 1
+    ppu.vram[0x1010] = 0x4080;
+    ppu.cgram[0] = 0x7C00;
+    ppu.cgram[17] = 0x001F;
+    ppu.cgram[18] = 0x03E0;
+    ppu.write(0x00, 0x0F);
+}
+
+#[test]
+fn vram_ports() {
+    let mut ppu = Ppu::new();
+    // Increment by 1 after writing the high byte
+    ppu.write(0x15, 0x80);
+    write_word(&mut ppu, 0x16, 0x1234);
+    write_word(&mut ppu, 0x18, 0xABCD);
+    write_word(&mut ppu, 0x18, 0x0123);
+    assert_eq!([0xABCD, 0x0123], ppu.vram[0x1234..0x1236]);
+    assert_eq!(0x1236, ppu.vram_address);
+    // Increment by 32 after writing the low byte
+    ppu.write(0x15, 0x01);
+    ppu.write(0x18, 0x56);
+    ppu.write(0x19, 0x78);
+    assert_eq!(0x0056, ppu.vram[0x1236]);
+    assert_eq!(0x7800, ppu.vram[0x1256]);
+    // Remapping rotates the low 8 bits of the address
+    ppu.write(0x15, 0x84);
+    write_word(&mut ppu, 0x16, 0x0021);
+    write_word(&mut ppu, 0x18, 0x4242);
+    assert_eq!(0x4242, ppu.vram[0x0009]);
+}
+
+#[test]
+fn vram_prefetch() {
+    let mut ppu = Ppu::new();
+    ppu.vram[0x2000..0x2004].copy_from_slice(&[0x1111, 0x2222, 0x3333, 0x4444]);
+    // Setting the address fills the latch, and reads refill it before
+    // incrementing the address
+    write_word(&mut ppu, 0x16, 0x2000);
+    let low: Vec<u8> = (0..4).map(|_| ppu.read(0x39, 0)).collect();
+    assert_eq!(vec![0x11, 0x11, 0x22, 0x33], low);
+    // Reads of the other byte don't touch the latch
+    assert_eq!(0x44, ppu.read(0x3A, 0));
+    assert_eq!(0x44, ppu.read(0x3A, 0));
+    assert_eq!(0x2004, ppu.vram_address);
+}
+
+#[test]
+fn cgram_and_oam_latches() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x21, 0x05);
+    ppu.write(0x22, 0x34);
+    assert_eq!(0, ppu.cgram[5]);
+    ppu.write(0x22, 0xFF);
+    assert_eq!(0x7F34, ppu.cgram[5]);
+    ppu.write(0x21, 0x05);
+    assert_eq!(0x34, ppu.read(0x3B, 0));
+    assert_eq!(0x7F, ppu.read(0x3B, 0));
+    // The low table is written in pairs, the high table one byte at a time
+    write_word(&mut ppu, 0x02, 0x0001);
+    ppu.write(0x04, 0xAA);
+    assert_eq!(0, ppu.oam[2]);
+    ppu.write(0x04, 0xBB);
+    assert_eq!([0xAA, 0xBB], ppu.oam[2..4]);
+    write_word(&mut ppu, 0x02, 0x0100);
+    ppu.write(0x04, 0xCC);
+    assert_eq!(0xCC, ppu.oam[0x200]);
+    write_word(&mut ppu, 0x02, 0x0001);
+    assert_eq!(0xAA, ppu.read(0x38, 0));
+    assert_eq!(0xBB, ppu.read(0x38, 0));
+}
+
+#[test]
+fn scroll_latch() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x0D, 0xAB);
+    ppu.write(0x0D, 0x01);
+    ppu.write(0x14, 0xCD);
+    ppu.write(0x14, 0x02);
+    assert_eq!(0x1AB, ppu.hofs[0]);
+    assert_eq!(0x2CD, ppu.vofs[3]);
+    // A single write to horizontal scroll takes the low 3 bits from its
+    // own high bits, and the rest from the previous write
+    ppu.write(0x0E, 0xFF);
+    ppu.write(0x0D, 0x00);
+    assert_eq!(0x0F9, ppu.hofs[0]);
+}
+
+#[test]
+fn active_display() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x00, 0x0F);
+    ppu.write(0x15, 0x80);
+    ppu.set_position(100, 0);
+    write_word(&mut ppu, 0x18, 0x1234);
+    ppu.write(0x04, 0x56);
+    ppu.write(0x04, 0x78);
+    assert_eq!(0, ppu.vram[0]);
+    assert_eq!([0, 0], ppu.oam[..2]);
+    // The addresses still increment
+    assert_eq!(1, ppu.vram_address);
+    assert_eq!(2, ppu.oam_address);
+    ppu.set_position(230, 0);
+    write_word(&mut ppu, 0x18, 0x1234);
+    assert_eq!(0x1234, ppu.vram[1]);
+    // Forced blanking frees memory during active display
+    ppu.set_position(100, 0);
+    ppu.write(0x00, 0x80);
+    write_word(&mut ppu, 0x18, 0x5678);
+    assert_eq!(0x5678, ppu.vram[2]);
+}
+
+#[test]
+fn counter_latch() {
+    let mut ppu = Ppu::new();
+    ppu.set_position(0x123, 0x45);
+    assert_eq!(0xFF, ppu.read(0x37, 0xFF));
+    // High bytes take their other bits from open bus of PPU2
+    assert_eq!(0x45, ppu.read(0x3C, 0));
+    assert_eq!(0x44, ppu.read(0x3C, 0));
+    assert_eq!(0x23, ppu.read(0x3D, 0));
+    assert_eq!(0x23, ppu.read(0x3D, 0));
+    // STAT78 reports the latch once
+    assert_eq!(0x63, ppu.read(0x3F, 0));
+    assert_eq!(0x23, ppu.read(0x3F, 0));
+    assert_eq!(0x45, ppu.read(0x3C, 0));
+}
+
+#[test]
+fn render_background() {
+    let mut ppu = Ppu::new();
+    setup_mode_1(&mut ppu);
+    assert_eq!([0xFF0000, 0x00FF00, 0x0000FF], first_line(&mut ppu)[..3]);
+    // Flipped horizontally, at half brightness
+    ppu.vram[0x0400] = 0x4401;
+    ppu.write(0x00, 0x07);
+    assert_eq!([0x00007F, 0x007F00, 0x7F0000], first_line(&mut ppu)[5..8]);
+    // Forced blanking
+    ppu.write(0x00, 0x8F);
+    assert!(first_line(&mut ppu).iter().all(|&color| color == 0));
+}
+
+#[test]
+fn bg3_priority() {
+    let mut ppu = Ppu::new();
+    setup_mode_1(&mut ppu);
+    // BG3 covers the first tile with color 1 at high priority
+    ppu.write(0x09, 0x08);
+    ppu.write(0x0C, 0x02);
+    ppu.write(0x12, 0xFF);
+    ppu.write(0x12, 0x03);
+    ppu.write(0x2C, 0x05);
+    ppu.vram[0x0800] = 0x2001;
+    ppu.vram[0x2008] = 0x00FF;
+    ppu.cgram[1] = 0x7FFF;
+    assert_eq!([0xFF0000, 0x00FF00, 0xFFFFFF], first_line(&mut ppu)[..3]);
+    ppu.write(0x05, 0x09);
+    assert_eq!([0xFFFFFF, 0xFFFFFF, 0xFFFFFF], first_line(&mut ppu)[..3]);
+}
+
+#[test]
+fn large_tiles() {
+    let mut ppu = Ppu::new();
+    setup_mode_1(&mut ppu);
+    // 16×16 tiles in a 64×32 tilemap, scrolled to its second screen
+    ppu.write(0x05, 0x11);
+    ppu.write(0x07, 0x05);
+    ppu.write(0x0D, 0x00);
+    ppu.write(0x0D, 0x02);
+    ppu.vram[0x0400] = 0;
+    ppu.vram[0x0800] = 0x0401;
+    // Tile 2 is the top right quarter
+    ppu.vram[0x1020] = 0x0080;
+    let line = first_line(&mut ppu);
+    assert_eq!([0xFF0000, 0x00FF00, 0x0000FF], line[..3]);
+    assert_eq!([0x0000FF, 0xFF0000, 0x0000FF], line[7..10]);
+}
+
+#[test]
+fn offset_per_tile() {
+    let mut ppu = Ppu::new();
+    setup_mode_1(&mut ppu);
+    ppu.write(0x05, 0x02);
+    // The BG3 tilemap at $0800 scrolls the third column of BG1 to the
+    // start of its tilemap, and the fourth one only for BG2
+    ppu.write(0x09, 0x08);
+    ppu.vram[0x0801] = 0x23F0;
+    ppu.vram[0x0802] = 0x43F0;
+    let line = first_line(&mut ppu);
+    assert_eq!([0xFF0000, 0x00FF00, 0x0000FF], line[..3]);
+    assert_eq!([0x0000FF; 3], line[8..11]);
+    assert_eq!([0xFF0000, 0x00FF00, 0x0000FF], line[16..19]);
+    assert_eq!([0x0000FF; 3], line[24..27]);
+}
+
+#[test]
+fn high_resolution() {
+    let mut ppu = Ppu::new();
+    setup_mode_1(&mut ppu);
+    ppu.write(0x05, 0x05);
+    // Tiles are 16 dots wide, with tile 2 as their right half
+    ppu.vram[0x1020] = 0x0080;
+    let row = first_row(&mut ppu);
+    assert_eq!([0xFF0000, 0x00FF00, 0x0000FF], row[..3]);
+    assert_eq!([0xFF0000, 0x0000FF], row[8..10]);
+    assert_eq!([0x0000FF; 2], row[510..]);
+    // Scrolling moves by two dots
+    ppu.hofs[0] = 0x3FF;
+    let row = first_row(&mut ppu);
+    assert_eq!([0x0000FF, 0x0000FF, 0xFF0000, 0x00FF00], row[..4]);
+    assert_eq!([0xFF0000, 0x0000FF], row[10..12]);
+}
+
+#[test]
+fn direct_color() {
+    let mut ppu = Ppu::new();
+    setup_mode_1(&mut ppu);
+    // Mode 3 with tile 1 of BG1 in palette 7, at 64 bytes per tile
+    ppu.write(0x05, 0x03);
+    ppu.vram[0x0400] = 0x1C01;
+    ppu.vram[0x1020] = 0x80C0;
+    for &address in &[0x1028, 0x1030, 0x1038] {
+        ppu.vram[address] = 0x8080;
+    }
+    ppu.cgram[0x01] = 0x001F;
+    ppu.cgram[0xFF] = 0x7FFF;
+    assert_eq!([0xFFFFFF, 0xFF0000, 0x0000FF], first_line(&mut ppu)[..3]);
+    // Colors 0xFF and 0x01, with the palette adding the lowest bits
+    ppu.write(0x30, 0x01);
+    assert_eq!([0xF7F7E7, 0x311021, 0x0000FF], first_line(&mut ppu)[..3]);
+}
+
+#[test]
+fn mode_7_multiply() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x1B, 0x34);
+    ppu.write(0x1B, 0x12);
+    ppu.write(0x1C, 0x00);
+    ppu.write(0x1C, 0xFE);
+    // 0x1234 * -2
+    assert_eq!([0x98, 0xDB, 0xFF],
+               [ppu.read(0x34, 0), ppu.read(0x35, 0), ppu.read(0x36, 0)]);
+}
+
+/// Sets up mode 7 without any transformation, with tile 1 at the top left
+/// corner, showing color 5 at its dot 0 of row 1. Tile 0 shows color 6
+/// there.
+fn setup_mode_7(ppu: &mut Ppu) {
+    ppu.write(0x05, 0x07);
+    ppu.write(0x2C, 0x01);
+    // M7A and M7D of 1.0
+    for &address in &[0x1B, 0x1E] {
+        ppu.write(address, 0x00);
+        ppu.write(address, 0x01);
+    }
+    ppu.vram[0x0000] = 0x0001;
+    ppu.vram[0x0008] = 0x0600;
+    ppu.vram[0x0048] = 0x0500;
+    ppu.cgram[0] = 0x7C00;
+    ppu.cgram[5] = 0x001F;
+    ppu.cgram[6] = 0x03E0;
+    ppu.write(0x00, 0x0F);
+}
+
+#[test]
+fn mode_7() {
+    let mut ppu = Ppu::new();
+    setup_mode_7(&mut ppu);
+    assert_eq!([0xFF0000, 0x0000FF], first_line(&mut ppu)[..2]);
+    ppu.write(0x1A, 0x01);
+    assert_eq!([0x0000FF, 0xFF0000], first_line(&mut ppu)[254..]);
+    // Scrolled 8 dots to the left of the tilemap, where it repeats with
+    // tile 1 in the rightmost column
+    ppu.write(0x0D, 0xF8);
+    ppu.write(0x0D, 0x1F);
+    ppu.vram[0x007F] = 0x0001;
+    for &(m7sel, outside) in &[(0x00, 0xFF0000), (0x80, 0x0000FF), (0xC0, 0x00FF00)] {
+        ppu.write(0x1A, m7sel);
+        assert_eq!([outside, 0xFF0000], [first_line(&mut ppu)[0], first_line(&mut ppu)[8]]);
+    }
+}
+
+#[test]
+fn mode_7_extbg() {
+    let mut ppu = Ppu::new();
+    setup_mode_7(&mut ppu);
+    // BG2 uses the top bit as its priority
+    ppu.vram[0x0048] = 0x8500;
+    ppu.cgram[0x85] = 0x7FFF;
+    ppu.write(0x33, 0x40);
+    assert_eq!(0xFFFFFF, first_line(&mut ppu)[0]);
+    ppu.write(0x2C, 0x03);
+    assert_eq!(0xFF0000, first_line(&mut ppu)[0]);
+    ppu.write(0x2C, 0x02);
+    ppu.vram[0x0048] = 0x0500;
+    assert_eq!(0xFF0000, first_line(&mut ppu)[0]);
+}
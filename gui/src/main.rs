@@ -53,7 +53,7 @@ fn gui() -> Result<(), Box<Error + Send + Sync>> {
     while window.is_open() {
         let start = Instant::now();
 
-        emulator.run_frame(&mut buffer);
+        emulator.run_frame(&mut buffer)?;
 
         window.update_with_buffer(&buffer);
         let elapsed = start.elapsed();